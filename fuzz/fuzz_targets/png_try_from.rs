@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use png_me::Png;
+
+// Any input must either parse or return a PngError, never panic
+fuzz_target!(|data: &[u8]| {
+    if let Ok(png) = Png::try_from(data) {
+        let _ = png.as_bytes();
+        for chunk in png.chunks() {
+            let _ = chunk.data_as_string();
+            let _ = chunk.chunk_type().to_string();
+        }
+    }
+});
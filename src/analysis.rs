@@ -0,0 +1,720 @@
+use std::{fmt::Display, io::Cursor};
+
+use crate::{error::Result, ihdr::ColorType, pixels::PixelBuffer, png::Png, reader::PngReader};
+
+// Chunk types defined by the PNG specification and its registered extensions
+const KNOWN_CHUNK_TYPES: [&str; 31] = [
+    "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV",
+    "cLLI", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME", "acTL", "fcTL",
+    "fdAT", "oFFs", "pCAL", "sCAL", "sTER", "gIFg", "gIFx",
+];
+// Ancillary chunks whose data is compressed, so high entropy is expected
+const COMPRESSED_CHUNK_TYPES: [&str; 4] = ["zTXt", "iTXt", "iCCP", "fdAT"];
+// Entropy estimates of shorter data are too noisy to tell ciphertext from text
+const MIN_ENTROPY_LENGTH: usize = 256;
+// Bits per byte above which data looks compressed or encrypted
+const HIGH_ENTROPY: f64 = 7.5;
+// Chi-square p-value above which the LSB pairs of values look equalized by embedding
+const CHI_SQUARE_THRESHOLD: f64 = 0.95;
+// The chi-square test is repeated on growing prefixes of the samples, 5% at a time
+const CHI_SQUARE_STEPS: usize = 20;
+// RS embedding rates from which the LSBs are reported. Clean 8-bit PngSuite images, which are
+// synthetic and break RS analysis' assumptions, score up to 0.47.
+const RS_MEDIUM_RATE: f64 = 0.5;
+const RS_HIGH_RATE: f64 = 0.8;
+// Fewer groups than this leave the RS estimate to sampling noise
+const RS_MIN_GROUPS: usize = 256;
+// RS analysis looks at groups of this many horizontally adjacent samples
+const RS_GROUP_LENGTH: usize = 4;
+const RS_MASK: [bool; RS_GROUP_LENGTH] = [false, true, true, false];
+
+/// How strongly a finding suggests hidden data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Something in the file that could hide data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub description: String,
+}
+
+/// Statistical tests on the least-significant bits of the color samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LsbStatistics {
+    /// Number of color samples tested, alpha is left out
+    pub samples: usize,
+    /// Probability that the LSB pairs of values (2k, 2k+1) are as even as random embedding
+    /// makes them, over the whole image. `None` when too few values repeat for the test.
+    pub chi_square_p_value: Option<f64>,
+    /// Fraction of the samples, from the start of the image, over which the chi-square test
+    /// finds equalized pairs: the extent of sequential LSB embedding. Images with a smooth
+    /// histogram look equalized throughout, embedding or not.
+    pub equalized_prefix: f64,
+    /// Fraction of samples whose LSB RS analysis estimates to carry data, from 0 to 1.
+    /// `None` for 16-bit images, where the test's assumptions about neighboring samples don't
+    /// hold and every image scores 100%, and for images with too few groups of samples.
+    pub rs_embedding_rate: Option<f64>,
+}
+
+/// What `analyze` found in one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    /// `None` for indexed and sub-byte images, where LSBs aren't used to hide data
+    pub lsb_statistics: Option<LsbStatistics>,
+}
+
+impl Report {
+    /// The most severe finding, `None` when nothing looks suspicious
+    pub fn suspicion(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+}
+
+/// Audits a PNG file for places that could hide data: private, unknown or malformed chunk types,
+/// data after `IEND`, high-entropy ancillary chunks and LSBs that look like they were replaced.
+pub fn analyze(file: &[u8]) -> Result<Report> {
+    let mut reader = PngReader::new(Cursor::new(file))?;
+    let mut chunks = Vec::new();
+    let mut found_end = false;
+    while let Some(chunk) = reader.read_chunk()? {
+        found_end = chunk.chunk_type().bytes() == *b"IEND";
+        chunks.push(chunk);
+        if found_end {
+            break;
+        }
+    }
+    let trailing_length = file.len() - reader.into_inner().position() as usize;
+    let png = Png::from_chunks(chunks);
+
+    let mut findings = chunk_findings(&png);
+    if trailing_length > 0 {
+        findings.push(Finding {
+            severity: Severity::High,
+            description: format!("{} bytes of data after IEND", trailing_length),
+        });
+    } else if !found_end {
+        findings.push(Finding {
+            severity: Severity::Medium,
+            description: "the file has no IEND chunk".to_string(),
+        });
+    }
+
+    let lsb_statistics = match png.pixels() {
+        Ok(pixels) => lsb_statistics(&pixels),
+        Err(err) => {
+            findings.push(Finding {
+                severity: Severity::Medium,
+                description: format!("the image data can't be decoded: {}", err),
+            });
+            None
+        }
+    };
+    if let Some(statistics) = &lsb_statistics {
+        findings.extend(lsb_findings(statistics));
+    }
+
+    Ok(Report {
+        findings,
+        lsb_statistics,
+    })
+}
+
+/// One finding per suspicious chunk type, from the `ChunkType` property bits and chunk contents
+fn chunk_findings(png: &Png) -> Vec<Finding> {
+    let mut chunk_types = Vec::new();
+    for chunk in png.chunks() {
+        if !chunk_types.contains(chunk.chunk_type()) {
+            chunk_types.push(*chunk.chunk_type());
+        }
+    }
+
+    let mut findings = Vec::new();
+    for chunk_type in chunk_types {
+        let name = chunk_type.to_string();
+        let data: Vec<u8> = png
+            .chunks_by_type(&name)
+            .flat_map(|chunk| chunk.data())
+            .copied()
+            .collect();
+        let count = png.chunks_by_type(&name).count();
+        let summary = format!(
+            "{} ({} chunk{}, {} bytes)",
+            name,
+            count,
+            if count == 1 { "" } else { "s" },
+            data.len()
+        );
+        let known = KNOWN_CHUNK_TYPES.contains(&name.as_str());
+
+        if !chunk_type.is_reserved_bit_valid() {
+            findings.push(Finding {
+                severity: Severity::High,
+                description: format!("{} has the reserved bit set, no valid PNG uses it", summary),
+            });
+        } else if !chunk_type.is_public() {
+            // Decoders must reject unknown critical chunks, so a private one only suits custom readers
+            findings.push(Finding {
+                severity: if chunk_type.is_critical() {
+                    Severity::High
+                } else {
+                    Severity::Medium
+                },
+                description: format!("private chunk {}", summary),
+            });
+        } else if !known {
+            findings.push(Finding {
+                severity: if chunk_type.is_critical() {
+                    Severity::High
+                } else {
+                    Severity::Low
+                },
+                description: format!("unknown public chunk {}", summary),
+            });
+        }
+
+        if !chunk_type.is_critical()
+            && !COMPRESSED_CHUNK_TYPES.contains(&name.as_str())
+            && data.len() >= MIN_ENTROPY_LENGTH
+        {
+            let entropy = entropy(&data);
+            if entropy >= HIGH_ENTROPY {
+                findings.push(Finding {
+                    severity: Severity::Medium,
+                    description: format!(
+                        "{} holds high-entropy data ({:.2} bits per byte), likely compressed or encrypted",
+                        summary, entropy
+                    ),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Shannon entropy of `data` in bits per byte, with the Miller-Madow correction for the
+/// underestimate that comes from a limited number of bytes
+fn entropy(data: &[u8]) -> f64 {
+    let mut histogram = [0usize; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+    let length = data.len() as f64;
+    let observed = histogram.iter().filter(|&&count| count > 0).count();
+    let plug_in: f64 = histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum();
+    plug_in + (observed as f64 - 1.0) / (2.0 * length * std::f64::consts::LN_2)
+}
+
+fn lsb_findings(statistics: &LsbStatistics) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // Smooth histograms have even pairs everywhere, so only a change partway through the image
+    // is evidence; embedding that covers the whole image is left to RS analysis
+    if statistics.equalized_prefix > 0.0 && statistics.equalized_prefix < 1.0 {
+        findings.push(Finding {
+            severity: Severity::High,
+            description: format!(
+                "chi-square test: LSB pairs of values are equalized over the first {:.0}% of \
+                 the samples but not the rest, typical of sequential LSB embedding",
+                statistics.equalized_prefix * 100.0
+            ),
+        });
+    }
+    if let Some(rate) = statistics.rs_embedding_rate {
+        if rate >= RS_MEDIUM_RATE {
+            findings.push(Finding {
+                severity: if rate >= RS_HIGH_RATE {
+                    Severity::High
+                } else {
+                    Severity::Medium
+                },
+                description: format!(
+                    "RS analysis: about {:.0}% of the color samples' LSBs carry data",
+                    rate * 100.0
+                ),
+            });
+        }
+    }
+    findings
+}
+
+/// Runs the chi-square and RS tests on the color samples, `None` for images whose samples
+/// are palette indices or shorter than a byte
+fn lsb_statistics(pixels: &PixelBuffer) -> Option<LsbStatistics> {
+    let ihdr = pixels.ihdr();
+    if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 {
+        return None;
+    }
+    let color_channels = match ihdr.color_type {
+        ColorType::GrayscaleAlpha | ColorType::Rgba => ihdr.color_type.samples_per_pixel() - 1,
+        _ => ihdr.color_type.samples_per_pixel(),
+    };
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+    // Samples in the order sequential LSB embedding fills them
+    let samples: Vec<u16> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| (0..color_channels).map(move |channel| pixels.sample(x, y, channel)))
+        .collect();
+    let low_bytes: Vec<u8> = samples.iter().map(|&sample| sample as u8).collect();
+
+    // Prefixes too short for the test are skipped rather than ending the equalized run
+    let mut equalized_steps = 0;
+    for step in 1..=CHI_SQUARE_STEPS {
+        let prefix = &low_bytes[..low_bytes.len() * step / CHI_SQUARE_STEPS];
+        match chi_square_p_value(prefix) {
+            Some(p_value) if p_value > CHI_SQUARE_THRESHOLD => equalized_steps = step,
+            Some(_) => break,
+            None => {}
+        }
+    }
+
+    Some(LsbStatistics {
+        samples: samples.len(),
+        chi_square_p_value: chi_square_p_value(&low_bytes),
+        equalized_prefix: equalized_steps as f64 / CHI_SQUARE_STEPS as f64,
+        rs_embedding_rate: if ihdr.bit_depth == 8 {
+            rs_embedding_rate(&low_bytes, width * color_channels, color_channels)
+        } else {
+            None
+        },
+    })
+}
+
+/// Westfeld and Pfitzmann's chi-square attack: replacing LSBs with random bits makes the
+/// counts of each pair of values (2k, 2k+1) converge, which the test measures against the
+/// pairs' average. Returns the probability that the counts are that close by chance.
+fn chi_square_p_value(values: &[u8]) -> Option<f64> {
+    let mut histogram = [0usize; 256];
+    for &value in values {
+        histogram[value as usize] += 1;
+    }
+    let mut chi_square = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        // The test is only meaningful for pairs that occur often enough
+        if expected < 5.0 {
+            continue;
+        }
+        chi_square += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return None;
+    }
+    let degrees_of_freedom = (categories - 1) as f64;
+    Some(regularized_gamma_q(
+        degrees_of_freedom / 2.0,
+        chi_square / 2.0,
+    ))
+}
+
+/// Fridrich, Goljan and Du's RS analysis. Groups of adjacent samples are classified as regular or
+/// singular depending on whether flipping LSBs (F1: 2k <-> 2k+1) or shifted LSBs (F-1: 2k-1 <-> 2k)
+/// makes them noisier. In natural images both flips behave alike; LSB embedding pulls the F1
+/// counts together while pushing the F-1 counts apart, in proportion to the embedding rate.
+fn rs_embedding_rate(samples: &[u8], row_length: usize, channels: usize) -> Option<f64> {
+    let mut groups = Vec::new();
+    for row in samples.chunks_exact(row_length) {
+        for channel in 0..channels {
+            let channel_samples: Vec<i32> = row[channel..]
+                .iter()
+                .step_by(channels)
+                .map(|&sample| i32::from(sample))
+                .collect();
+            groups.extend(
+                channel_samples
+                    .chunks_exact(RS_GROUP_LENGTH)
+                    .map(|group| <[i32; RS_GROUP_LENGTH]>::try_from(group).unwrap()),
+            );
+        }
+    }
+    if groups.len() < RS_MIN_GROUPS {
+        return None;
+    }
+
+    let flipped: Vec<[i32; RS_GROUP_LENGTH]> = groups
+        .iter()
+        .map(|group| group.map(flip_positive))
+        .collect();
+    let (regular, singular) = rs_counts(&groups, flip_positive);
+    let (negative_regular, negative_singular) = rs_counts(&groups, flip_negative);
+    let (flipped_regular, flipped_singular) = rs_counts(&flipped, flip_positive);
+    let (flipped_negative_regular, flipped_negative_singular) = rs_counts(&flipped, flip_negative);
+
+    let d0 = regular - singular;
+    let d1 = flipped_regular - flipped_singular;
+    let negative_d0 = negative_regular - negative_singular;
+    let negative_d1 = flipped_negative_regular - flipped_negative_singular;
+    let a = 2.0 * (d1 + d0);
+    let b = negative_d0 - negative_d1 - d1 - 3.0 * d0;
+    let c = d0 - negative_d0;
+
+    let z = if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON {
+            return Some(0.0);
+        }
+        -c / b
+    } else {
+        let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+        let (z1, z2) = ((-b + root) / (2.0 * a), (-b - root) / (2.0 * a));
+        if z1.abs() < z2.abs() {
+            z1
+        } else {
+            z2
+        }
+    };
+    Some((z / (z - 0.5)).clamp(0.0, 1.0))
+}
+
+/// Fractions of regular and singular groups once `flip` is applied to the masked samples
+fn rs_counts(groups: &[[i32; RS_GROUP_LENGTH]], flip: fn(i32) -> i32) -> (f64, f64) {
+    let (mut regular, mut singular) = (0, 0);
+    for group in groups {
+        let mut flipped = *group;
+        for (sample, masked) in flipped.iter_mut().zip(RS_MASK) {
+            if masked {
+                *sample = flip(*sample);
+            }
+        }
+        match smoothness(&flipped).cmp(&smoothness(group)) {
+            std::cmp::Ordering::Greater => regular += 1,
+            std::cmp::Ordering::Less => singular += 1,
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    let total = groups.len() as f64;
+    (regular as f64 / total, singular as f64 / total)
+}
+
+/// Sum of the differences between neighboring samples, larger for noisier groups
+fn smoothness(group: &[i32]) -> i32 {
+    group.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum()
+}
+
+fn flip_positive(sample: i32) -> i32 {
+    sample ^ 1
+}
+
+fn flip_negative(sample: i32) -> i32 {
+    ((sample + 1) ^ 1) - 1
+}
+
+/// Q(a, x) = Γ(a, x) / Γ(a), the upper tail of the chi-square distribution with 2a degrees of
+/// freedom at 2x, computed with a series below a + 1 and a continued fraction above
+fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 1000;
+    const TOLERANCE: f64 = 1e-12;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefactor = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * TOLERANCE {
+                break;
+            }
+        }
+        (1.0 - sum * log_prefactor.exp()).clamp(0.0, 1.0)
+    } else {
+        // Modified Lentz evaluation of the continued fraction
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut fraction = d;
+        for n in 1..MAX_ITERATIONS {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.0).abs() < TOLERANCE {
+                break;
+            }
+        }
+        (fraction * log_prefactor.exp()).clamp(0.0, 1.0)
+    }
+}
+
+/// Natural logarithm of the gamma function, with the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, coefficient)| {
+            sum + coefficient / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::Chunk,
+        chunk_type::ChunkType,
+        ihdr::{Ihdr, Interlace},
+        lsb::{self, LsbOptions},
+        pixels::EncodeOptions,
+        test_util::{self, XorShift},
+    };
+    use std::str::FromStr;
+
+    /// A smooth RGB image with mild sensor-like noise, which is what LSB embedding usually targets
+    fn cover_image() -> PixelBuffer {
+        let (width, height) = (128, 128);
+        let ihdr = Ihdr::new(width, height, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let mut rng = XorShift(0x1234_5678);
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                for channel in 0..3 {
+                    let smooth = 110.0
+                        + 60.0 * ((x as f64 / 17.0) + channel as f64).sin()
+                        + 40.0 * (y as f64 / 23.0).cos()
+                        + 0.3 * x as f64;
+                    let noise = (0..3).map(|_| (rng.next() % 5) as f64 - 2.0).sum::<f64>() / 2.0;
+                    data.push((smooth + noise).round().clamp(0.0, 255.0) as u8);
+                }
+            }
+        }
+        PixelBuffer::new(ihdr, data).unwrap()
+    }
+
+    fn png_bytes(pixels: &PixelBuffer, extra_chunks: Vec<Chunk>) -> Vec<u8> {
+        let mut png = Png::from_pixels(pixels, None, EncodeOptions::default()).unwrap();
+        for chunk in extra_chunks {
+            png.insert_before("IEND", chunk).unwrap();
+        }
+        png.as_bytes()
+    }
+
+    /// Like `cover_image`, but posterized to even values with a few odd ones, so the pairs of
+    /// values (2k, 2k+1) start out very uneven
+    fn posterized_cover_image() -> PixelBuffer {
+        let mut pixels = cover_image();
+        let mut rng = XorShift(0x0bad_cafe);
+        for sample in pixels.data_mut() {
+            *sample = (*sample & !1) | u8::from(rng.next().is_multiple_of(8));
+        }
+        pixels
+    }
+
+    fn lsb_fill(pixels: &mut PixelBuffer, fraction: f64) {
+        let capacity = lsb::capacity(pixels.ihdr(), &LsbOptions::default()).unwrap();
+        let payload = test_util::noise(0x9e37_79b9, (capacity as f64 * fraction) as usize);
+        lsb::embed(pixels, &payload, &LsbOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn test_clean_image_is_not_suspicious() {
+        let report = analyze(&png_bytes(&cover_image(), Vec::new())).unwrap();
+        assert_eq!(report.suspicion(), None, "{:?}", report.findings);
+        let statistics = report.lsb_statistics.unwrap();
+        assert_eq!(statistics.samples, 128 * 128 * 3);
+        assert!(statistics.rs_embedding_rate.unwrap() < RS_MEDIUM_RATE);
+
+        // Posterizing throws off RS analysis, but not the chi-square test
+        let report = analyze(&png_bytes(&posterized_cover_image(), Vec::new())).unwrap();
+        let statistics = report.lsb_statistics.unwrap();
+        assert!(statistics.chi_square_p_value.unwrap() < 0.01);
+        assert_eq!(statistics.equalized_prefix, 0.0);
+    }
+
+    #[test]
+    fn test_rs_analysis_estimates_embedding_rate() {
+        for fraction in [0.25, 0.5, 1.0] {
+            let mut pixels = cover_image();
+            lsb_fill(&mut pixels, fraction);
+            let report = analyze(&png_bytes(&pixels, Vec::new())).unwrap();
+            let rate = report.lsb_statistics.unwrap().rs_embedding_rate.unwrap();
+            assert!(
+                (rate - fraction).abs() < 0.15,
+                "{} estimated as {}",
+                fraction,
+                rate
+            );
+        }
+
+        let mut pixels = cover_image();
+        lsb_fill(&mut pixels, 1.0);
+        let report = analyze(&png_bytes(&pixels, Vec::new())).unwrap();
+        assert_eq!(report.suspicion(), Some(Severity::High));
+    }
+
+    #[test]
+    fn test_rs_analysis_skips_16_bit_and_small_images() {
+        let ihdr = Ihdr::new(128, 128, 16, ColorType::Grayscale, Interlace::None).unwrap();
+        let pixels = PixelBuffer::new(ihdr, test_util::noise(0x2545_f491, 128 * 128 * 2)).unwrap();
+        let report = analyze(&png_bytes(&pixels, Vec::new())).unwrap();
+        assert_eq!(report.lsb_statistics.unwrap().rs_embedding_rate, None);
+
+        // 8x8 RGB has 48 groups of four samples
+        let ihdr = Ihdr::new(8, 8, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let pixels = PixelBuffer::new(ihdr, test_util::noise(0x2545_f491, 8 * 8 * 3)).unwrap();
+        let report = analyze(&png_bytes(&pixels, Vec::new())).unwrap();
+        assert_eq!(report.lsb_statistics.unwrap().rs_embedding_rate, None);
+    }
+
+    #[test]
+    fn test_clean_pngsuite_lsbs_are_not_suspicious() {
+        for (name, bytes) in test_util::pngsuite()
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with('x'))
+        {
+            let report = analyze(&bytes).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            let lsb_findings = report
+                .lsb_statistics
+                .map_or_else(Vec::new, |statistics| lsb_findings(&statistics));
+            assert!(lsb_findings.is_empty(), "{}: {:?}", name, lsb_findings);
+        }
+    }
+
+    #[test]
+    fn test_chi_square_finds_sequential_embedding() {
+        let mut pixels = posterized_cover_image();
+        lsb_fill(&mut pixels, 0.4);
+        let report = analyze(&png_bytes(&pixels, Vec::new())).unwrap();
+        let statistics = report.lsb_statistics.unwrap();
+        assert!(
+            (0.3..=0.4).contains(&statistics.equalized_prefix),
+            "{}",
+            statistics.equalized_prefix
+        );
+        assert!(report
+            .findings
+            .iter()
+            .any(|finding| finding.description.starts_with("chi-square")));
+    }
+
+    #[test]
+    fn test_chunk_findings() {
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::try_new(ChunkType::from_str(chunk_type).unwrap(), data).unwrap()
+        };
+        let pixels = cover_image();
+        let text = b"a perfectly ordinary comment ".repeat(20);
+        let bytes = png_bytes(
+            &pixels,
+            vec![
+                chunk("tEXt", [b"Comment\0".to_vec(), text].concat()),
+                chunk("ruSt", b"hidden".to_vec()),
+                chunk("ruSt", b"more".to_vec()),
+                chunk("xYZw", test_util::noise(0x9e37_79b9, 1000)),
+                chunk("abcd", Vec::new()),
+                chunk("SeCr", Vec::new()),
+            ],
+        );
+        let report = analyze(&bytes).unwrap();
+        let severities: Vec<(Severity, &str)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.severity, &finding.description[..]))
+            .collect();
+        assert_eq!(
+            severities,
+            [
+                (Severity::Medium, "private chunk ruSt (2 chunks, 10 bytes)"),
+                (
+                    Severity::Low,
+                    "unknown public chunk xYZw (1 chunk, 1000 bytes)"
+                ),
+                (
+                    Severity::Medium,
+                    "xYZw (1 chunk, 1000 bytes) holds high-entropy data (7.97 bits per byte), \
+                     likely compressed or encrypted"
+                ),
+                (
+                    Severity::High,
+                    "abcd (1 chunk, 0 bytes) has the reserved bit set, no valid PNG uses it"
+                ),
+                (Severity::High, "private chunk SeCr (1 chunk, 0 bytes)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut bytes = png_bytes(&cover_image(), Vec::new());
+        bytes.extend_from_slice(b"appended secret");
+        let report = analyze(&bytes).unwrap();
+        assert_eq!(
+            report.findings,
+            [Finding {
+                severity: Severity::High,
+                description: "15 bytes of data after IEND".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert!(entropy(&[0; 1000]) < 0.01);
+        assert!(entropy(&b"the quick brown fox jumps over the lazy dog ".repeat(20)) < 5.0);
+        assert!(entropy(&test_util::noise(0x9e37_79b9, 256)) > HIGH_ENTROPY);
+        assert!(entropy(&test_util::noise(0x9e37_79b9, 65536)) > 7.99);
+    }
+
+    #[test]
+    fn test_regularized_gamma_q() {
+        for x in [0.1, 1.0, 5.0, 20.0] {
+            assert!((regularized_gamma_q(1.0, x) - f64::exp(-x)).abs() < 1e-10);
+        }
+        // 95th percentiles of the chi-square distribution with 2 and 10 degrees of freedom
+        assert!((regularized_gamma_q(1.0, 5.991 / 2.0) - 0.05).abs() < 1e-4);
+        assert!((regularized_gamma_q(5.0, 18.307 / 2.0) - 0.05).abs() < 1e-4);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+    }
+}
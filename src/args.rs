@@ -1,4 +1,5 @@
-use clap::{arg, command, Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use png_me::{compression, fragment, lsb::LsbOptions, Chunk};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -14,35 +15,166 @@ pub enum Commands {
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    Info(InfoArgs),
+    Analyze(AnalyzeArgs),
+    Capacity(CapacityArgs),
+    Keygen(KeygenArgs),
+    Verify(VerifyArgs),
 }
 // an enum in Rust can have variants that include data, and the data can be of any type, including structs, tuples, or even other enums. The () in the enum variant indicates that the variant holds data of a specific type. For example, below example shows that Shape enum will have Circle which is type f64
 
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("payload").required(true).args(["message", "payload_file", "stdin"])))]
 pub struct EncodeArgs {
     #[arg(short, long)]
     pub in_file_path: PathBuf,
+    #[arg(short, long, required_unless_present_any = ["keyword", "method"])]
+    pub chunk_type: Option<String>,
+    /// Where the message is hidden: in a chunk of `--chunk-type`, in the pixels' lowest bits, or
+    /// after `IEND`
+    #[arg(long, value_enum, default_value_t = Method::Chunk)]
+    pub method: Method,
+    /// Passphrase choosing which samples carry the message with `--method lsb`; without it they
+    /// are used in order and anyone can read the message back
+    #[arg(long)]
+    pub stego_key: Option<String>,
+    /// With `--method lsb`, hide this many bits in each block of 2^N-1 samples by changing at most
+    /// one of them (Hamming matrix embedding). Higher values change fewer samples but hold less.
+    /// Defaults to 1, a bit per sample.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=LsbOptions::MAX_MATRIX_BITS as i64)
+    )]
+    pub matrix_bits: Option<u8>,
+    /// Store the message as a standard text chunk under this keyword, e.g. `Comment`.
+    /// The chunk type defaults to tEXt, or zTXt/iTXt for compressed or non-Latin-1 text.
+    #[arg(short, long, conflicts_with_all = ["payload_file", "stdin", "passphrase", "recipients", "sign_key", "raw", "method"])]
+    pub keyword: Option<String>,
     #[arg(short, long)]
-    pub chunk_type: String,
-    #[arg(short, long)]
-    pub message: String,
+    pub message: Option<String>,
+    /// Embed this file, keeping its name and MIME type
+    #[arg(short = 'f', long)]
+    pub payload_file: Option<PathBuf>,
+    /// Embed the data read from stdin
+    #[arg(long)]
+    pub stdin: bool,
     #[arg(short, long)]
     pub out_file_path: PathBuf,
+    /// Encrypt the message with a key derived from this passphrase
+    #[arg(short, long, conflicts_with = "recipients")]
+    pub passphrase: Option<String>,
+    /// Encrypt the message to this X25519 public key (hex), can be repeated
+    #[arg(short, long = "recipient")]
+    pub recipients: Vec<String>,
+    /// Sign the message with the Ed25519 private key in this file, see `keygen --signing`
+    #[arg(short, long)]
+    pub sign_key: Option<PathBuf>,
+    /// Make the signature also cover the image's critical chunks
+    #[arg(long, requires = "sign_key")]
+    pub sign_image: bool,
+    /// Compress the message before encrypting and embedding it
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    pub compress: Compression,
+    /// Store the message without the container header, for readers that predate it. Files
+    /// can't be stored raw, only the container marks them as files.
+    #[arg(long, conflicts_with_all = ["compress", "payload_file", "stdin"])]
+    pub raw: bool,
+    /// Split the message across several chunks whose data is at most this many bytes long
+    #[arg(
+        long,
+        default_value_t = Chunk::MAX_LENGTH as u32,
+        value_parser = clap::value_parser!(u32).range(fragment::HEADER_LENGTH as i64 + 1..=Chunk::MAX_LENGTH as i64)
+    )]
+    pub max_chunk_size: u32,
 }
 
 #[derive(Parser, Debug)]
 pub struct DecodeArgs {
     #[arg(short, long)]
     pub in_file_path: PathBuf,
+    #[arg(short, long, required_unless_present = "method")]
+    pub chunk_type: Option<String>,
+    /// Where the message was hidden, see `encode --method`
+    #[arg(long, value_enum, default_value_t = Method::Chunk)]
+    pub method: Method,
+    /// Passphrase given to `encode --stego-key`
+    #[arg(long)]
+    pub stego_key: Option<String>,
+    /// Value given to `encode --matrix-bits`
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=LsbOptions::MAX_MATRIX_BITS as i64)
+    )]
+    pub matrix_bits: Option<u8>,
+    /// How chunk data that is not UTF-8 text gets printed
+    #[arg(short, long, value_enum, default_value_t = BinaryFormat::Hex)]
+    pub binary_format: BinaryFormat,
+    /// Decrypt messages that were encoded with this passphrase
+    #[arg(short, long, conflicts_with = "identity")]
+    pub passphrase: Option<String>,
+    /// Decrypt messages with the X25519 private key in this file, see `keygen`
+    #[arg(long)]
+    pub identity: Option<PathBuf>,
+    /// Write the message to this file instead of printing it. For embedded files,
+    /// an existing directory receives the file under its original name.
     #[arg(short, long)]
-    pub chunk_type: String,
+    pub output: Option<PathBuf>,
+    /// Output the message bytes as stored in the image, without checking, decrypting,
+    /// decompressing or unpacking them
+    #[arg(long, conflicts_with_all = ["passphrase", "identity"])]
+    pub raw: bool,
+    /// Refuse to decompress messages that would grow larger than this many bytes
+    #[arg(long, default_value_t = compression::DEFAULT_MAX_DECOMPRESSED_LENGTH)]
+    pub max_decompressed_size: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// A dedicated chunk, split across several when it is too large
+    Chunk,
+    /// The least-significant bits of the color samples; the image data is re-encoded
+    Lsb,
+    /// Trailing data after `IEND`, which decoders ignore; needs `--passphrase`, whose encryption
+    /// authenticates it
+    Trailer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Compression {
+    None,
+    Zlib,
+    Zstd,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Encryption {
+    None,
+    Passphrase,
+    Recipients,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum BinaryFormat {
+    Hex,
+    Base64,
 }
 
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("output").required(true).args(["out_file_path", "in_place"])))]
 pub struct RemoveArgs {
     #[arg(short, long)]
     pub in_file_path: PathBuf,
     #[arg(short, long)]
     pub chunk_type: String,
+    /// Write the modified PNG to this file
+    #[arg(short, long)]
+    pub out_file_path: Option<PathBuf>,
+    /// Atomically replace the input file with the modified PNG
+    #[arg(long)]
+    pub in_place: bool,
+    /// Remove every chunk of the given type instead of only the first one
+    #[arg(short, long)]
+    pub all: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -50,3 +182,79 @@ pub struct PrintArgs {
     #[arg(short, long)]
     pub in_file_path: PathBuf,
 }
+
+#[derive(Parser, Debug)]
+pub struct InfoArgs {
+    #[arg(short, long)]
+    pub in_file_path: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct AnalyzeArgs {
+    /// PNG to audit for hidden data, can be repeated
+    #[arg(short, long = "in-file-path", required = true)]
+    pub in_file_paths: Vec<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CapacityArgs {
+    #[arg(short, long)]
+    pub in_file_path: PathBuf,
+    /// Also tell whether this file fits with each method, counting its name and MIME type
+    #[arg(short = 'f', long)]
+    pub payload_file: Option<PathBuf>,
+    /// Compression `encode` would apply; incompressible content is assumed
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    pub compress: Compression,
+    /// Encryption `encode` would apply
+    #[arg(long, value_enum, default_value_t = Encryption::None)]
+    pub encryption: Encryption,
+    /// Number of recipients with `--encryption recipients`, each adds a wrapped key
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub recipient_count: u16,
+    /// Count the overhead of a signature, see `encode --sign-key`
+    #[arg(long)]
+    pub sign: bool,
+    /// Leave out the container header, see `encode --raw`
+    #[arg(long, conflicts_with_all = ["compress", "payload_file"])]
+    pub raw: bool,
+    /// Largest chunk data `encode` would write, see `encode --max-chunk-size`
+    #[arg(
+        long,
+        default_value_t = Chunk::MAX_LENGTH as u32,
+        value_parser = clap::value_parser!(u32).range(fragment::HEADER_LENGTH as i64 + 1..=Chunk::MAX_LENGTH as i64)
+    )]
+    pub max_chunk_size: u32,
+    /// Bits per block of LSB samples, see `encode --matrix-bits`
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=LsbOptions::MAX_MATRIX_BITS as i64)
+    )]
+    pub matrix_bits: u8,
+}
+
+#[derive(Parser, Debug)]
+pub struct KeygenArgs {
+    /// File for the new private key, the public key is written next to it with a `.pub` extension
+    #[arg(short, long)]
+    pub out_file_path: PathBuf,
+    /// Generate an Ed25519 signing key instead of an X25519 encryption key
+    #[arg(short, long)]
+    pub signing: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("trusted").required(true).multiple(true).args(["trusted_keys", "trusted_keys_file"])))]
+pub struct VerifyArgs {
+    #[arg(short, long)]
+    pub in_file_path: PathBuf,
+    #[arg(short, long)]
+    pub chunk_type: String,
+    /// Ed25519 public key (hex) whose signatures are accepted, can be repeated
+    #[arg(short, long = "trusted-key")]
+    pub trusted_keys: Vec<String>,
+    /// File with one trusted Ed25519 public key (hex) per line, `#` starts a comment
+    #[arg(long = "trusted-keys")]
+    pub trusted_keys_file: Option<PathBuf>,
+}
@@ -0,0 +1,75 @@
+use crate::error::{PngError, Result};
+
+/// Cursor over a byte slice for parsing the big-endian headers stored inside chunk data.
+///
+/// Every read is bounds checked and returns `PngError::Truncated` instead of panicking.
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data }
+    }
+
+    pub(crate) fn read_bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.data.len() < length {
+            return Err(PngError::Truncated {
+                needed: length,
+                available: self.data.len(),
+            });
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Everything that has not been read yet
+    pub(crate) fn rest(self) -> &'a [u8] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_fields() {
+        let data = [1, 0, 0, 0, 2, 3, 9, 9];
+        let mut reader = ByteReader::new(&data);
+
+        assert_eq!(reader.read_u8().unwrap(), 1);
+        assert_eq!(reader.read_u32().unwrap(), 2);
+        assert_eq!(reader.read_bytes(1).unwrap(), &[3]);
+        assert_eq!(reader.rest(), &[9, 9]);
+    }
+
+    #[test]
+    fn test_read_past_end() {
+        let data = [1, 2];
+        let mut reader = ByteReader::new(&data);
+
+        assert!(matches!(
+            reader.read_u32(),
+            Err(PngError::Truncated {
+                needed: 4,
+                available: 2
+            })
+        ));
+    }
+}
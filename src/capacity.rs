@@ -0,0 +1,279 @@
+use crate::{
+    chunk::Chunk,
+    compression::{self, Codec},
+    container, crypto,
+    error::{PngError, Result},
+    fragment,
+    ihdr::Ihdr,
+    lsb::{self, LsbOptions},
+    signing, trailer,
+};
+
+/// Encryption applied to a message, of which only the added bytes matter here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encryption {
+    #[default]
+    None,
+    Passphrase,
+    /// Encryption to this many recipients
+    Recipients(usize),
+}
+
+/// The layers wrapped around the content before it is hidden, in the order they are applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Framing {
+    /// `FilePayload::header_length` for embedded files, 0 for text messages
+    pub file_header_length: usize,
+    pub compression: Option<Codec>,
+    pub encryption: Encryption,
+    pub signed: bool,
+    /// Leave out the container header, for readers that predate it
+    pub raw: bool,
+}
+
+impl Framing {
+    /// Length of the message carrying `content_length` bytes of content. Compression is assumed
+    /// not to shrink the content, so this is an upper bound when it is enabled.
+    pub fn message_length(&self, content_length: usize) -> usize {
+        let mut length = self.file_header_length + content_length;
+        if let Some(codec) = self.compression {
+            length = compression::max_compressed_length(length, codec);
+        }
+        length += match self.encryption {
+            Encryption::None => 0,
+            Encryption::Passphrase => crypto::PASSPHRASE_OVERHEAD,
+            Encryption::Recipients(count) => crypto::recipients_overhead(count),
+        };
+        if self.signed {
+            length += signing::OVERHEAD;
+        }
+        if !self.raw {
+            length += container::HEADER_LENGTH;
+        }
+        length
+    }
+
+    /// Bytes the framing adds to empty content
+    pub fn overhead(&self) -> usize {
+        self.message_length(0)
+    }
+
+    /// Most content whose message is at most `message_length` bytes long, `None` when not even
+    /// empty content fits
+    pub fn max_content_length(&self, message_length: usize) -> Option<usize> {
+        if self.overhead() > message_length {
+            return None;
+        }
+        // Framing never shrinks the content, so the answer is at most `message_length`
+        let (mut fits, mut too_long) = (0, message_length + 1);
+        while too_long - fits > 1 {
+            let middle = fits + (too_long - fits) / 2;
+            if self.message_length(middle) <= message_length {
+                fits = middle;
+            } else {
+                too_long = middle;
+            }
+        }
+        Some(fits)
+    }
+}
+
+/// Content a carrier can hold with LSB embedding at one rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LsbEstimate {
+    pub bits_per_sample: u8,
+    /// `None` when not even an empty message fits
+    pub content_length: Option<usize>,
+}
+
+/// How much content each hiding method can carry in one image, after framing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Estimate {
+    /// Framing added to the content
+    pub overhead: usize,
+    /// Framing added to content stored after `IEND`, which has no size limit. Trailers are always
+    /// encrypted with a passphrase, so this counts that encryption in place of `framing`'s, plus
+    /// the trailer header.
+    pub trailer_overhead: usize,
+    /// Content that fits in a single chunk, without fragment headers
+    pub chunk_content_length: Option<usize>,
+    /// Message bytes each chunk holds once the message is split into fragments, so there is no
+    /// limit on the content. `None` when chunks are too small for a fragment header.
+    pub fragment_data_length: Option<usize>,
+    /// One entry per supported bits per sample, empty for indexed and sub-byte images
+    pub lsb: Vec<LsbEstimate>,
+}
+
+/// Estimates how much content the image with this header can carry with `framing`: in ancillary
+/// chunks of at most `max_chunk_length` bytes, in the LSBs of its samples with
+/// `lsb_options.matrix_bits` at each supported bits per sample, and after `IEND`.
+pub fn estimate(
+    ihdr: &Ihdr,
+    framing: &Framing,
+    max_chunk_length: usize,
+    lsb_options: &LsbOptions,
+) -> Result<Estimate> {
+    let max_chunk_length = max_chunk_length.min(Chunk::MAX_LENGTH);
+    let mut lsb = Vec::new();
+    for bits_per_sample in 1..=LsbOptions::MAX_BITS_PER_SAMPLE {
+        let options = LsbOptions {
+            bits_per_sample,
+            ..lsb_options.clone()
+        };
+        match lsb::capacity(ihdr, &options) {
+            Ok(capacity) => lsb.push(LsbEstimate {
+                bits_per_sample,
+                content_length: framing.max_content_length(capacity),
+            }),
+            Err(PngError::UnsupportedCarrier(_)) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(Estimate {
+        overhead: framing.overhead(),
+        trailer_overhead: Framing {
+            encryption: Encryption::Passphrase,
+            ..framing.clone()
+        }
+        .overhead()
+            + trailer::HEADER_LENGTH,
+        chunk_content_length: framing.max_content_length(max_chunk_length),
+        fragment_data_length: (max_chunk_length > fragment::HEADER_LENGTH)
+            .then(|| max_chunk_length - fragment::HEADER_LENGTH),
+        lsb,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ihdr::{ColorType, Interlace},
+        payload::FilePayload,
+    };
+
+    #[test]
+    fn test_message_length_matches_layers() {
+        let content = vec![0x42; 1000];
+        let file = FilePayload {
+            file_name: Some("notes.txt".to_string()),
+            mime_type: Some("text/plain".to_string()),
+            data: content.clone(),
+        };
+        let signing_key = signing::SigningKey::generate();
+        let framing = Framing {
+            file_header_length: file.header_length(),
+            encryption: Encryption::Recipients(1),
+            signed: true,
+            ..Framing::default()
+        };
+
+        let mut message = file.to_bytes().unwrap();
+        message =
+            crypto::encrypt_to_recipients(&message, &[crypto::Identity::generate().recipient()])
+                .unwrap();
+        message = signing::sign(&message, &signing_key, None);
+        message = container::wrap(&message, container::Flags::default()).unwrap();
+        assert_eq!(framing.message_length(content.len()), message.len());
+
+        let raw = Framing {
+            raw: true,
+            ..Framing::default()
+        };
+        assert_eq!(raw.message_length(content.len()), content.len());
+        assert_eq!(raw.overhead(), 0);
+    }
+
+    #[test]
+    fn test_compression_is_an_upper_bound() {
+        let content = b"a highly repetitive message ".repeat(100);
+        let framing = Framing {
+            compression: Some(Codec::Zstd),
+            encryption: Encryption::Passphrase,
+            ..Framing::default()
+        };
+        let compressed = compression::compress(&content, Codec::Zstd).unwrap();
+        assert!(framing.message_length(content.len()) > content.len());
+        assert!(
+            framing.message_length(content.len())
+                >= compressed.len() + crypto::PASSPHRASE_OVERHEAD + container::HEADER_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_max_content_length() {
+        let framing = Framing {
+            compression: Some(Codec::Zlib),
+            signed: true,
+            ..Framing::default()
+        };
+        for limit in [0, framing.overhead(), 500, 100_000] {
+            match framing.max_content_length(limit) {
+                Some(length) => {
+                    assert!(framing.message_length(length) <= limit);
+                    assert!(framing.message_length(length + 1) > limit);
+                }
+                None => assert!(framing.overhead() > limit),
+            }
+        }
+        assert_eq!(framing.max_content_length(0), None);
+        assert_eq!(framing.max_content_length(framing.overhead()), Some(0));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let ihdr = Ihdr::new(64, 48, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let framing = Framing::default();
+        let estimate = estimate(&ihdr, &framing, 1000, &LsbOptions::default()).unwrap();
+
+        assert_eq!(estimate.overhead, container::HEADER_LENGTH);
+        assert_eq!(
+            estimate.trailer_overhead,
+            crypto::PASSPHRASE_OVERHEAD + container::HEADER_LENGTH + trailer::HEADER_LENGTH
+        );
+        assert_eq!(
+            estimate.chunk_content_length,
+            Some(1000 - container::HEADER_LENGTH)
+        );
+        assert_eq!(
+            estimate.fragment_data_length,
+            Some(1000 - fragment::HEADER_LENGTH)
+        );
+        // 9216 samples hold 1152 bytes at one bit each and 2304 at two, minus the length prefix
+        assert_eq!(
+            estimate.lsb,
+            vec![
+                LsbEstimate {
+                    bits_per_sample: 1,
+                    content_length: Some(1148 - container::HEADER_LENGTH),
+                },
+                LsbEstimate {
+                    bits_per_sample: 2,
+                    content_length: Some(2300 - container::HEADER_LENGTH),
+                },
+            ]
+        );
+
+        let estimate = estimate_for(ColorType::Indexed, 10);
+        assert!(estimate.lsb.is_empty());
+        assert_eq!(estimate.chunk_content_length, None);
+        assert_eq!(estimate.fragment_data_length, None);
+
+        let estimate = estimate_for(ColorType::Rgba, Chunk::MAX_LENGTH + 1);
+        assert_eq!(
+            estimate.fragment_data_length,
+            Some(Chunk::MAX_LENGTH - fragment::HEADER_LENGTH)
+        );
+    }
+
+    fn estimate_for(color_type: ColorType, max_chunk_length: usize) -> Estimate {
+        let ihdr = Ihdr::new(16, 16, 8, color_type, Interlace::None).unwrap();
+        estimate(
+            &ihdr,
+            &Framing::default(),
+            max_chunk_length,
+            &LsbOptions::default(),
+        )
+        .unwrap()
+    }
+}
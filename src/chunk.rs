@@ -1,9 +1,11 @@
 use core::str;
 use std::fmt::Display;
+use std::io::Write;
 
 use crate::chunk_type::ChunkType;
+use crate::error::PngError;
 
-const ALGORITHM_CRC: crc::Algorithm<u32> = crc::CRC_32_ISO_HDLC; // This algorithm is the one for the Unit Tests
+pub(crate) const ALGORITHM_CRC: crc::Algorithm<u32> = crc::CRC_32_ISO_HDLC; // This algorithm is the one for the Unit Tests
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -14,7 +16,26 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// Bytes taken by the length, chunk type and CRC fields around the data
+    pub const METADATA_LENGTH: usize = 12;
+    /// Largest data length the PNG specification allows, 2^31-1 bytes
+    pub const MAX_LENGTH: usize = i32::MAX as usize;
+
+    /// Builds a chunk and computes its CRC without checking the data length: data longer than
+    /// `Chunk::MAX_LENGTH` gives a chunk whose length field is wrong.
+    #[deprecated(note = "use `Chunk::try_new`, which rejects data longer than `Chunk::MAX_LENGTH`")]
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        Chunk::build(chunk_type, data)
+    }
+    /// Builds a chunk and computes its CRC, or returns `PngError::ChunkTooLarge` if `data` is
+    /// longer than `Chunk::MAX_LENGTH`
+    pub fn try_new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Chunk, PngError> {
+        if data.len() > Chunk::MAX_LENGTH {
+            return Err(PngError::ChunkTooLarge(data.len()));
+        }
+        Ok(Chunk::build(chunk_type, data))
+    }
+    fn build(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
         let crc = crc::Crc::<u32>::new(&ALGORITHM_CRC);
         let data_to_crc: Vec<u8> = chunk_type
             .bytes()
@@ -43,51 +64,78 @@ impl Chunk {
     pub fn crc(&self) -> u32 {
         self.crc
     }
-    pub fn data_as_string(&self) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(str::from_utf8(&self.message_bytes).unwrap().to_string())
+    pub fn data_as_string(&self) -> Result<String, PngError> {
+        Ok(str::from_utf8(&self.message_bytes)?.to_string())
     }
     pub fn as_bytes(&self) -> Vec<u8> {
-        (self.message_bytes.len() as u32) // as u32 needed else it returns [u8;8] instead of [u8;4]
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.message_bytes.iter())
-            .chain(self.crc().to_be_bytes().iter())
-            .cloned()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.message_bytes.len() + Chunk::METADATA_LENGTH);
+        bytes.extend_from_slice(&self.data_length.to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&self.message_bytes);
+        bytes.extend_from_slice(&self.crc.to_be_bytes());
+        bytes
+    }
+    /// Length, chunk type and data without the CRC, which is derived from them.
+    /// This is the representation signatures are computed over.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.as_bytes();
+        bytes.truncate(bytes.len() - 4);
+        bytes
+    }
+    /// Serializes the chunk straight into `writer`, without building it in memory first
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), PngError> {
+        writer.write_all(&self.data_length.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.message_bytes)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
+        Ok(())
     }
 }
 
 impl TryFrom<&[u8]> for Chunk {
-    type Error = &'static str;
+    type Error = PngError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        // Every length check happens before slicing, so malformed input never panics
+        if value.len() < 8 {
+            return Err(PngError::Truncated {
+                needed: 8,
+                available: value.len(),
+            });
+        }
         let (split_data_length, remaining_after_length) = value.split_at(4);
-        let data_length = u32::from_be_bytes(
+        let data_length = u32::from_be_bytes([
             // passes with be, fails with le
-            split_data_length
-                .try_into()
-                .expect("slice with incorrect length"),
-        ); // convert from &[u8] to u32 in rust
+            split_data_length[0],
+            split_data_length[1],
+            split_data_length[2],
+            split_data_length[3],
+        ]); // convert from &[u8] to u32 in rust
 
         let (split_type_code, remaining_after_type) = remaining_after_length.split_at(4);
-        let chunk_type = ChunkType {
-            type_code: [
-                split_type_code[0],
-                split_type_code[1],
-                split_type_code[2],
-                split_type_code[3],
-            ],
-        };
+        let chunk_type = ChunkType::try_from([
+            split_type_code[0],
+            split_type_code[1],
+            split_type_code[2],
+            split_type_code[3],
+        ])?;
+
+        if data_length as usize > Chunk::MAX_LENGTH {
+            return Err(PngError::ChunkTooLarge(data_length as usize));
+        }
+        // Data length is declared by the input, so it can be larger than what is left
+        let needed = (data_length as usize).saturating_add(Chunk::METADATA_LENGTH);
+        if value.len() < needed {
+            return Err(PngError::Truncated {
+                needed,
+                available: value.len(),
+            });
+        }
 
         let (split_message_bytes, remaining_after_data) =
-            remaining_after_type.split_at(data_length.try_into().unwrap());
+            remaining_after_type.split_at(data_length as usize);
         let message_bytes: Vec<u8> = split_message_bytes.to_vec();
+        let split_crc = &remaining_after_data[..4];
 
-        if remaining_after_data.len() < 4 {
-            return Err("Wrong data length");
-        }
-
-        let (split_crc, _remaining_after_crc) = remaining_after_data.split_at(4);
         let crc = crc::Crc::<u32>::new(&ALGORITHM_CRC);
         let data_to_crc: Vec<u8> = chunk_type
             .bytes()
@@ -97,10 +145,14 @@ impl TryFrom<&[u8]> for Chunk {
             .collect();
         let crc_val_computed = crc.checksum(&data_to_crc);
 
-        let crc_val_from_bytes = u32::from_be_bytes(split_crc.try_into().expect("Crc Error"));
+        let crc_val_from_bytes =
+            u32::from_be_bytes([split_crc[0], split_crc[1], split_crc[2], split_crc[3]]);
         // The CRC from the calculation and from the last 4 bytes should match
         if crc_val_computed != crc_val_from_bytes {
-            return Err("Crc values do not match");
+            return Err(PngError::CrcMismatch {
+                expected: crc_val_computed,
+                actual: crc_val_from_bytes,
+            });
         };
 
         let chunk = Chunk {
@@ -142,6 +194,7 @@ mod tests {
         Chunk::try_from(chunk_data.as_ref()).unwrap()
     }
     #[test]
+    #[allow(deprecated)]
     fn test_new_chunk() {
         let chunk_type = ChunkType::from_str("RuSt").unwrap();
         let data = "This is where your secret message will be!"
@@ -213,6 +266,46 @@ mod tests {
         assert!(chunk.is_err());
     }
     #[test]
+    fn test_canonical_bytes() {
+        let chunk = testing_chunk();
+        let canonical_bytes = chunk.canonical_bytes();
+        assert_eq!(canonical_bytes.len(), 4 + 4 + 42);
+        assert_eq!(&canonical_bytes[..], &chunk.as_bytes()[..50]);
+    }
+    #[test]
+    fn test_crc_mismatch_error() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 1;
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+        assert!(matches!(
+            chunk,
+            Err(PngError::CrcMismatch {
+                expected: 2882656334,
+                actual: 2882656335
+            })
+        ));
+    }
+    #[test]
+    fn test_declared_length_too_large() {
+        let mut chunk_data = vec![0x80, 0, 0, 0];
+        chunk_data.extend_from_slice(b"RuSt");
+        chunk_data.extend_from_slice(&[0; 4]);
+        assert!(matches!(
+            Chunk::try_from(chunk_data.as_ref()),
+            Err(PngError::ChunkTooLarge(0x8000_0000))
+        ));
+    }
+    #[test]
+    fn test_invalid_utf8_error() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::try_new(chunk_type, vec![0xff, 0xfe]).unwrap();
+        assert!(matches!(
+            chunk.data_as_string(),
+            Err(PngError::InvalidUtf8(_))
+        ));
+    }
+    #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
         let chunk_type = "RuSt".as_bytes();
@@ -2,9 +2,11 @@ use core::str;
 use std::fmt::Display;
 use std::str::FromStr;
 
+use crate::error::PngError;
+
 #[derive(Debug, Clone, Copy)]
 pub struct ChunkType {
-    pub type_code: [u8; 4], // Chunk Type: A 4-byte chunk type code
+    type_code: [u8; 4], // Chunk Type: A 4-byte chunk type code
 }
 
 impl ChunkType {
@@ -12,52 +14,36 @@ impl ChunkType {
         self.type_code
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         // Ancillary bit: bit 5 of first byte
         // 0 (uppercase) = critical, 1 (lowercase) = ancillary.
         let bytes_type_code = self.bytes();
         // let ancillary_bit = bytes_type_code[0] >> 4 & 1;
         // Uppercase- true, lowercase- false
-        if bytes_type_code[0].is_ascii_uppercase() {
-            true
-        } else {
-            false
-        }
+        bytes_type_code[0].is_ascii_uppercase()
     }
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         // Private bit: bit 5 of second byte
         // 0 (uppercase) = public, 1 (lowercase) = private.
         let bytes_type_code = self.bytes();
         // Uppercase- true, lowercase- false
-        if bytes_type_code[1].is_ascii_uppercase() {
-            true
-        } else {
-            false
-        }
+        bytes_type_code[1].is_ascii_uppercase()
     }
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         // Reserved bit: bit 5 of third byte
         // Must be 0 (uppercase) in files conforming to this version of PNG.
         let bytes_type_code = self.bytes();
         // Uppercase- true, lowercase- false
-        if bytes_type_code[2].is_ascii_uppercase() {
-            true
-        } else {
-            false
-        }
+        bytes_type_code[2].is_ascii_uppercase()
     }
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         // Safe-to-copy bit: bit 5 of fourth byte
         // 0 (uppercase) = unsafe to copy, 1 (lowercase) = safe to copy.
         let bytes_type_code = self.bytes();
         // Uppercase- false, lowercase- true
-        if bytes_type_code[3].is_ascii_uppercase() {
-            false
-        } else {
-            true
-        }
+        !bytes_type_code[3].is_ascii_uppercase()
     }
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         self.bytes().is_ascii()
             && self.is_reserved_bit_valid()
             && self.bytes()[0].is_ascii_alphabetic()
@@ -68,9 +54,14 @@ impl ChunkType {
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = &'static str;
+    type Error = PngError;
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
         // https://doc.rust-lang.org/std/convert/trait.TryFrom.html
+        if !value.iter().all(|byte| byte.is_ascii_alphabetic()) {
+            return Err(PngError::InvalidChunkType(
+                String::from_utf8_lossy(&value).to_string(),
+            ));
+        }
         let chunk = Self { type_code: value };
 
         Ok(chunk)
@@ -78,27 +69,27 @@ impl TryFrom<[u8; 4]> for ChunkType {
 }
 
 impl FromStr for ChunkType {
-    type Err = &'static str;
+    type Err = PngError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() != 4 {
-            return Err("String must be exactly 4 characters long");
+            return Err(PngError::InvalidChunkType(s.to_string()));
         }
         if !s.as_bytes()[0].is_ascii_alphabetic()
             || !s.as_bytes()[1].is_ascii_alphabetic()
             || !s.as_bytes()[2].is_ascii_alphabetic()
             || !s.as_bytes()[3].is_ascii_alphabetic()
         {
-            return Err("String not completely alphabetic");
+            return Err(PngError::InvalidChunkType(s.to_string()));
         }
         let bytes = s.trim().as_bytes();
         let bytes_array: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
-        ChunkType::try_from(bytes_array).map_err(|_| "Failed to convert to ChunkType")
+        ChunkType::try_from(bytes_array)
     }
 }
 
 impl Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", str::from_utf8(&self.type_code).unwrap().trim())
+        write!(f, "{}", String::from_utf8_lossy(&self.type_code).trim())
     }
 }
 
@@ -190,6 +181,13 @@ mod tests {
 
         let chunk = ChunkType::from_str("Ru1t");
         assert!(chunk.is_err());
+        assert!(matches!(chunk, Err(PngError::InvalidChunkType(_))));
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_invalid_bytes() {
+        let chunk = ChunkType::try_from([82, 0, 83, 255]);
+        assert!(matches!(chunk, Err(PngError::InvalidChunkType(_))));
     }
 
     #[test]
@@ -1,65 +1,1004 @@
-use std::str::FromStr;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    process::ExitCode,
+    str::FromStr,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use png_me::{
+    analysis,
+    capacity::{self, Framing},
+    compression::{self, Codec},
+    container::{self, Container},
+    crypto::{self, Identity, KdfParams, Recipient},
+    fragment,
+    lsb::{self, LsbOptions},
+    payload::FilePayload,
+    pixels::EncodeOptions,
+    signing::{self, SignedPayload, SigningKey, VerifyingKey},
+    text_chunk::{self, TextChunk, TextKind},
+    trailer, Chunk, ChunkType, Ihdr, Png, PngError, PngReader,
+};
 
-use crate::{
-    args::{Commands, DecodeArgs, EncodeArgs, PrintArgs, RemoveArgs},
-    chunk::Chunk,
-    chunk_type::ChunkType,
-    png::Png,
+use crate::args::{
+    AnalyzeArgs, BinaryFormat, CapacityArgs, Commands, Compression, DecodeArgs, EncodeArgs,
+    Encryption, InfoArgs, KeygenArgs, Method, PrintArgs, RemoveArgs, VerifyArgs,
 };
 
-pub fn execute_command(command: Commands) -> std::result::Result<(), Box<dyn std::error::Error>> {
+/// Exit code of `decode` when the requested chunk type does not exist, so scripts can tell it apart from other failures
+const EXIT_CHUNK_NOT_FOUND: u8 = 3;
+
+pub fn execute_command(command: Commands) -> Result<ExitCode, PngError> {
     match command {
-        Commands::Encode(encode_args) => execute_encode(encode_args),
-        Commands::Decode(decode_args) => execute_decode(decode_args),
-        Commands::Remove(remove_args) => execute_remove(remove_args),
-        Commands::Print(print_args) => execute_print(print_args),
+        Commands::Encode(encode_args) => execute_encode(encode_args)?,
+        Commands::Decode(decode_args) => return execute_decode(decode_args),
+        Commands::Remove(remove_args) => execute_remove(remove_args)?,
+        Commands::Print(print_args) => execute_print(print_args)?,
+        Commands::Info(info_args) => execute_info(info_args)?,
+        Commands::Analyze(analyze_args) => execute_analyze(analyze_args)?,
+        Commands::Capacity(capacity_args) => execute_capacity(capacity_args)?,
+        Commands::Keygen(keygen_args) => execute_keygen(keygen_args)?,
+        Commands::Verify(verify_args) => execute_verify(verify_args)?,
     }
+    Ok(ExitCode::SUCCESS)
 }
 
 /// cargo run --release -- encode --in-file-path assests/dice.png --chunk-type tEXt --message Hello --out-file-path assests/newdice.png
-fn execute_encode(encode_args: EncodeArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read(encode_args.in_file_path)?;
-    let mut png_image = Png::try_from(&file[..])?;
+/// cargo run --release -- encode --in-file-path assests/dice.png --chunk-type ruSt --payload-file notes.pdf --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --chunk-type ruSt --payload-file notes.pdf --max-chunk-size 65536 --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --chunk-type ruSt --payload-file notes.txt --compress zstd --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --keyword Comment --message Hello --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --method lsb --message Hello --passphrase hunter2 --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --method lsb --stego-key hunter3 --message Hello --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --method lsb --matrix-bits 3 --message Hello --out-file-path assests/newdice.png
+/// cargo run --release -- encode --in-file-path assests/dice.png --method trailer --message Hello --passphrase hunter2 --out-file-path assests/newdice.png
+fn execute_encode(encode_args: EncodeArgs) -> Result<(), PngError> {
+    let lsb_options = lsb_options(
+        encode_args.method,
+        encode_args.stego_key.as_ref(),
+        encode_args.matrix_bits,
+    )?;
+    check_chunk_type(encode_args.method, encode_args.chunk_type.as_deref())?;
+    // `verify` only finds signed messages in chunks
+    if encode_args.method != Method::Chunk && encode_args.sign_key.is_some() {
+        return Err(PngError::InvalidArgument(
+            "--sign-key only works with --method chunk, signatures are verified from chunks"
+                .to_string(),
+        ));
+    }
+    let mut png_image = Png::read_from(open_input(&encode_args.in_file_path)?)?;
 
-    let chunk_type_to_add = ChunkType::from_str(&encode_args.chunk_type)?;
-    let chunk_to_append = Chunk::new(chunk_type_to_add, encode_args.message.into());
+    let chunks_to_add = match (&encode_args.keyword, encode_args.method) {
+        (Some(keyword), _) => vec![text_chunk(keyword, &encode_args)?],
+        (None, Method::Chunk) => message_chunks(&png_image, &encode_args)?,
+        (None, Method::Lsb) => {
+            embed_in_pixels(&mut png_image, &encode_args, &lsb_options)?;
+            Vec::new()
+        }
+        (None, Method::Trailer) => {
+            append_trailer(&mut png_image, &encode_args)?;
+            Vec::new()
+        }
+    };
+    for chunk_to_append in chunks_to_add {
+        // Strict decoders reject data after IEND, so the message goes right before it
+        match png_image.insert_before("IEND", chunk_to_append.clone()) {
+            Err(PngError::ChunkNotFound(_)) => png_image.append_chunk(chunk_to_append),
+            result => result?,
+        }
+    }
 
-    png_image.append_chunk(chunk_to_append.clone());
+    let mut out_file = BufWriter::new(File::create(encode_args.out_file_path)?);
+    png_image.write_to(&mut out_file)
+}
 
-    Ok(std::fs::write(
-        encode_args.out_file_path,
-        png_image.as_bytes(),
-    )?)
+/// Standard text chunk that other PNG tools display, picked from `--chunk-type` and `--compress`
+fn text_chunk(keyword: &str, encode_args: &EncodeArgs) -> Result<Chunk, PngError> {
+    let mut text_chunk =
+        TextChunk::new(keyword, encode_args.message.as_deref().unwrap_or_default());
+    let compressed = match encode_args.compress {
+        Compression::None => false,
+        Compression::Zlib => true,
+        Compression::Zstd => {
+            return Err(PngError::InvalidTextChunk(
+                "text chunks can only be compressed with zlib".to_string(),
+            ))
+        }
+    };
+    let international = || TextKind::International {
+        compressed,
+        language_tag: String::new(),
+        translated_keyword: String::new(),
+    };
+    text_chunk.kind = match (encode_args.chunk_type.as_deref(), text_chunk.kind) {
+        (Some("tEXt"), _) if compressed => {
+            return Err(PngError::InvalidTextChunk(
+                "tEXt chunks can't be compressed, use zTXt".to_string(),
+            ))
+        }
+        (Some("tEXt"), _) => TextKind::Text,
+        (Some("zTXt"), _) => TextKind::Compressed,
+        (Some("iTXt"), _) => international(),
+        (Some(chunk_type), _) => {
+            return Err(PngError::InvalidTextChunk(format!(
+                "--keyword needs a tEXt, zTXt or iTXt chunk type, not {}",
+                chunk_type
+            )))
+        }
+        (None, TextKind::Text) if compressed => TextKind::Compressed,
+        (None, TextKind::Text) => TextKind::Text,
+        (None, _) => international(),
+    };
+    text_chunk.to_chunk()
 }
 
-/// cargo run --release -- decode --in-file-path assests/dice.png --chunk-type tEXt
-fn execute_decode(decode_args: DecodeArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read(decode_args.in_file_path)?;
-    let png_image = Png::try_from(&file[..])?;
+/// Chunks carrying the message, split to fit `--max-chunk-size`
+fn message_chunks(png_image: &Png, encode_args: &EncodeArgs) -> Result<Vec<Chunk>, PngError> {
+    let chunk_type_to_add = required_chunk_type(encode_args.chunk_type.as_deref())?;
+    // The carrier's critical chunks are signed before the message chunk is added
+    let image = encode_args
+        .sign_image
+        .then(|| image_bytes_for_signature(png_image, &chunk_type_to_add));
+    let message = build_message(encode_args, image.as_deref())?;
+    fragment::split(&message, encode_args.max_chunk_size as usize)?
+        .into_iter()
+        .map(|piece| Chunk::try_new(chunk_type_to_add, piece))
+        .collect()
+}
 
-    let chunk = png_image.chunk_by_type(&decode_args.chunk_type.as_str());
-    println!("Decoded chunk: {:?}", chunk);
+/// Hides the message in the least-significant bits of the pixels and re-encodes the image data
+fn embed_in_pixels(
+    png_image: &mut Png,
+    encode_args: &EncodeArgs,
+    options: &LsbOptions,
+) -> Result<(), PngError> {
+    let message = build_message(encode_args, None)?;
+    let mut pixels = png_image.pixels()?;
+    let modified = lsb::embed(&mut pixels, &message, options)?;
+    eprintln!(
+        "Embedded {} of {} available bytes, {} samples modified",
+        message.len(),
+        lsb::capacity(pixels.ihdr(), options)?,
+        modified
+    );
+    png_image.replace_pixels(&pixels, EncodeOptions::default())
+}
 
+/// Stores the message after `IEND`. No checksum covers trailing data, so the message has to be
+/// encrypted with a passphrase, whose authentication tag detects any change.
+fn append_trailer(png_image: &mut Png, encode_args: &EncodeArgs) -> Result<(), PngError> {
+    if encode_args.passphrase.is_none() {
+        return Err(PngError::InvalidArgument(
+            "--method trailer needs --passphrase, which authenticates the trailing data"
+                .to_string(),
+        ));
+    }
+    let trailing_data = png_image.trailing_data();
+    if !trailing_data.is_empty() && !trailer::is_trailer(trailing_data) {
+        return Err(PngError::InvalidArgument(format!(
+            "the image already has {} bytes of other data after IEND",
+            trailing_data.len()
+        )));
+    }
+    let message = build_message(encode_args, None)?;
+    png_image.set_trailing_data(trailer::wrap(&message)?);
     Ok(())
 }
 
-///cargo run --release -- remove --in-file-path assests/dice.png --chunk-type tEXt
-fn execute_remove(remove_args: RemoveArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read(remove_args.in_file_path)?;
-    let mut png_image = Png::try_from(&file[..])?;
+/// `--chunk-type` only names the chunk of `--method chunk`, the other methods would ignore it
+fn check_chunk_type(method: Method, chunk_type: Option<&str>) -> Result<(), PngError> {
+    if method != Method::Chunk && chunk_type.is_some() {
+        return Err(PngError::InvalidArgument(
+            "--chunk-type only applies to --method chunk".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    let chunk = png_image.remove_first_chunk(&remove_args.chunk_type.as_str())?;
-    println!("Chunk removed: {:?}", chunk);
+/// Options for `--method lsb`. They mean nothing to the other methods, so giving them there is an
+/// error rather than silently ignored.
+fn lsb_options(
+    method: Method,
+    stego_key: Option<&String>,
+    matrix_bits: Option<u8>,
+) -> Result<LsbOptions, PngError> {
+    if method != Method::Lsb && (stego_key.is_some() || matrix_bits.is_some()) {
+        return Err(PngError::InvalidArgument(
+            "--stego-key and --matrix-bits only apply to --method lsb".to_string(),
+        ));
+    }
+    let defaults = LsbOptions::default();
+    Ok(LsbOptions {
+        key: stego_key.cloned(),
+        matrix_bits: matrix_bits.unwrap_or(defaults.matrix_bits),
+        ..defaults
+    })
+}
+
+/// The message with the requested compression, encryption and signature applied. `image` is
+/// included in the signature when given.
+fn build_message(encode_args: &EncodeArgs, image: Option<&[u8]>) -> Result<Vec<u8>, PngError> {
+    let mut flags = container::Flags {
+        file: encode_args.payload_file.is_some() || encode_args.stdin,
+        ..container::Flags::default()
+    };
+    let mut message: Vec<u8> = if let Some(payload_file) = &encode_args.payload_file {
+        FilePayload::from_path(payload_file, std::fs::read(payload_file)?).to_bytes()?
+    } else if encode_args.stdin {
+        if encode_args.in_file_path == Path::new("-") {
+            return Err(PngError::InvalidArgument(
+                "stdin can't carry both the PNG and the payload".to_string(),
+            ));
+        }
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        FilePayload {
+            file_name: None,
+            mime_type: None,
+            data,
+        }
+        .to_bytes()?
+    } else {
+        encode_args.message.clone().unwrap_or_default().into()
+    };
+    if let Some(codec) = codec_for(encode_args.compress) {
+        message = compression::compress(&message, codec)?;
+        flags.compressed = true;
+    }
+    if let Some(passphrase) = &encode_args.passphrase {
+        message = crypto::encrypt_with_passphrase(&message, passphrase, KdfParams::default())?;
+        flags.encrypted = true;
+    } else if !encode_args.recipients.is_empty() {
+        let recipients = encode_args
+            .recipients
+            .iter()
+            .map(|recipient| Recipient::from_hex(recipient))
+            .collect::<Result<Vec<Recipient>, PngError>>()?;
+        message = crypto::encrypt_to_recipients(&message, &recipients)?;
+        flags.encrypted = true;
+    }
+    if let Some(sign_key_path) = &encode_args.sign_key {
+        let sign_key = SigningKey::from_hex(&std::fs::read_to_string(sign_key_path)?)?;
+        message = signing::sign(&message, &sign_key, image);
+        flags.signed = true;
+    }
+    if !encode_args.raw {
+        message = container::wrap(&message, flags)?;
+    }
+    Ok(message)
+}
 
+fn codec_for(compression: Compression) -> Option<Codec> {
+    match compression {
+        Compression::None => None,
+        Compression::Zlib => Some(Codec::Zlib),
+        Compression::Zstd => Some(Codec::Zstd),
+    }
+}
+
+/// `--chunk-type` is optional on the command line because other methods don't need it
+fn required_chunk_type(chunk_type: Option<&str>) -> Result<ChunkType, PngError> {
+    let chunk_type = chunk_type.ok_or_else(|| {
+        PngError::InvalidArgument("--chunk-type is required with --method chunk".to_string())
+    })?;
+    ChunkType::from_str(chunk_type)
+}
+
+/// cargo run --release -- decode --in-file-path assests/dice.png --chunk-type tEXt
+/// cargo run --release -- decode --in-file-path assests/newdice.png --chunk-type ruSt --output assests/
+/// cargo run --release -- decode --in-file-path assests/newdice.png --method lsb --passphrase hunter2
+/// cargo run --release -- decode --in-file-path assests/newdice.png --method trailer --passphrase hunter2
+/// cargo run --release -- decode --in-file-path assests/newdice.png --method lsb --stego-key hunter3
+/// cargo run --release -- decode --in-file-path assests/newdice.png --method lsb --matrix-bits 3
+fn execute_decode(decode_args: DecodeArgs) -> Result<ExitCode, PngError> {
+    let lsb_options = lsb_options(
+        decode_args.method,
+        decode_args.stego_key.as_ref(),
+        decode_args.matrix_bits,
+    )?;
+    check_chunk_type(decode_args.method, decode_args.chunk_type.as_deref())?;
+    let identity = match &decode_args.identity {
+        Some(identity_path) => Some(Identity::from_hex(&std::fs::read_to_string(
+            identity_path,
+        )?)?),
+        None => None,
+    };
+
+    let (text_chunks, messages) = match decode_args.method {
+        Method::Chunk => {
+            let chunk_type = required_chunk_type(decode_args.chunk_type.as_deref())?;
+            let (text_chunks, messages) = chunk_messages(
+                PngReader::new(open_input(&decode_args.in_file_path)?)?,
+                &chunk_type,
+            )?;
+            if text_chunks.is_empty() && messages.is_empty() {
+                eprintln!("Error: {}", PngError::ChunkNotFound(chunk_type.to_string()));
+                return Ok(ExitCode::from(EXIT_CHUNK_NOT_FOUND));
+            }
+            (text_chunks, messages)
+        }
+        Method::Lsb => {
+            let png_image = Png::read_from(open_input(&decode_args.in_file_path)?)?;
+            (
+                Vec::new(),
+                vec![lsb::extract(&png_image.pixels()?, &lsb_options)?],
+            )
+        }
+        Method::Trailer => {
+            if decode_args.passphrase.is_none() {
+                return Err(PngError::InvalidArgument(
+                    "--method trailer needs --passphrase, which authenticates the trailing data"
+                        .to_string(),
+                ));
+            }
+            let png_image = Png::read_from(open_input(&decode_args.in_file_path)?)?;
+            (
+                Vec::new(),
+                vec![trailer::parse(png_image.trailing_data())?.to_vec()],
+            )
+        }
+    };
+    if text_chunks.len() + messages.len() > 1 && decode_args.output.is_some() {
+        return Err(PngError::InvalidArgument(
+            "several messages match, --output can only hold one of them".to_string(),
+        ));
+    }
+
+    for text_chunk in &text_chunks {
+        match &decode_args.output {
+            Some(output) => std::fs::write(output, &text_chunk.text)?,
+            None => println!("{}: {}", text_chunk.keyword, text_chunk.text),
+        }
+    }
+    for message in &messages {
+        if decode_args.raw {
+            output_message(message, false, &decode_args)?;
+            continue;
+        }
+        // Legacy messages have no container, so their layers are recognized by their own headers
+        let (flags, mut data) = if container::is_container(message) {
+            let container = Container::parse(message)?;
+            (Some(container.flags()), container.content())
+        } else {
+            (None, &message[..])
+        };
+        if flags.map_or_else(|| signing::is_signed(data), |flags| flags.signed) {
+            let signed_payload = SignedPayload::parse(data)?;
+            eprintln!(
+                "Signed by {} (not verified, use the verify command)",
+                signed_payload.signer().to_hex()
+            );
+            data = signed_payload.payload();
+        }
+        // Legacy messages don't record encryption, so they are decrypted whenever a key is given
+        let encrypted = flags.map_or(
+            decode_args.passphrase.is_some() || identity.is_some(),
+            |flags| flags.encrypted,
+        );
+        // Anyone can append data to a file, only decryption shows that a trailer is genuine
+        if decode_args.method == Method::Trailer && !encrypted {
+            return Err(PngError::MalformedPayload(
+                "the trailer isn't encrypted, so it can't be authenticated".to_string(),
+            ));
+        }
+        let message = if !encrypted {
+            data.to_vec()
+        } else if let Some(passphrase) = &decode_args.passphrase {
+            crypto::decrypt_with_passphrase(data, passphrase)?
+        } else if let Some(identity) = &identity {
+            crypto::decrypt_with_identity(data, identity)?
+        } else {
+            return Err(PngError::InvalidArgument(
+                "the message is encrypted, pass --passphrase or --identity".to_string(),
+            ));
+        };
+        let message = if flags.is_some_and(|flags| flags.compressed) {
+            compression::decompress(&message, decode_args.max_decompressed_size)?
+        } else {
+            message
+        };
+        // Only the container marks files, legacy messages are always printed as they are
+        let is_file = flags.is_some_and(|flags| flags.file);
+        output_message(&message, is_file, &decode_args)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Text chunks and reassembled messages of `chunk_type`, keeping no other chunk while reading
+fn chunk_messages(
+    png_reader: PngReader<impl Read>,
+    chunk_type: &ChunkType,
+) -> Result<(Vec<TextChunk>, Vec<Vec<u8>>), PngError> {
+    let mut text_chunks = Vec::new();
+    let mut message_chunks = Vec::new();
+    for chunk in png_reader {
+        let chunk = chunk?;
+        if chunk.chunk_type() != chunk_type {
+            continue;
+        }
+        // Text chunk types can also hold messages from older versions, which have no keyword
+        match text_chunk::is_text_chunk_type(chunk_type).then(|| TextChunk::try_from(&chunk)) {
+            Some(Ok(text_chunk)) => text_chunks.push(text_chunk),
+            _ => message_chunks.push(chunk),
+        }
+    }
+    let messages = fragment::reassemble(message_chunks.iter().map(|chunk| chunk.data()))?;
+    Ok((text_chunks, messages))
+}
+
+/// Prints the message, or writes it to `--output`. Embedded files are unpacked with their metadata.
+fn output_message(message: &[u8], is_file: bool, decode_args: &DecodeArgs) -> Result<(), PngError> {
+    let file_payload = if is_file {
+        Some(FilePayload::parse(message)?)
+    } else {
+        None
+    };
+    if let Some(file_payload) = &file_payload {
+        eprintln!(
+            "Embedded file: {} ({}, {} bytes)",
+            file_payload.file_name.as_deref().unwrap_or("<unnamed>"),
+            file_payload.mime_type.as_deref().unwrap_or("unknown type"),
+            file_payload.data.len()
+        );
+    }
+    let data = file_payload
+        .as_ref()
+        .map_or(message, |file_payload| &file_payload.data[..]);
+
+    match &decode_args.output {
+        Some(output) => {
+            // A directory receives the file under its original name
+            let output_path = match file_payload
+                .as_ref()
+                .and_then(|file_payload| file_payload.safe_file_name())
+            {
+                Some(file_name) if output.is_dir() => output.join(file_name),
+                _ => output.clone(),
+            };
+            std::fs::write(&output_path, data)?;
+            eprintln!("Wrote {} bytes to {}", data.len(), output_path.display());
+        }
+        None => print_message(data, decode_args.binary_format),
+    }
     Ok(())
 }
 
+fn print_message(message: &[u8], binary_format: BinaryFormat) {
+    match std::str::from_utf8(message) {
+        Ok(text) if is_printable(text) => println!("{}", text),
+        // Binary data would garble the terminal, so it is printed encoded
+        _ => println!("{}", encode_binary(message, binary_format)),
+    }
+}
+
+fn is_printable(message: &str) -> bool {
+    message
+        .chars()
+        .all(|c| !c.is_control() || c.is_ascii_whitespace())
+}
+
+fn encode_binary(data: &[u8], binary_format: BinaryFormat) -> String {
+    match binary_format {
+        BinaryFormat::Hex => hex::encode(data),
+        BinaryFormat::Base64 => BASE64_STANDARD.encode(data),
+    }
+}
+
+///cargo run --release -- remove --in-file-path assests/dice.png --chunk-type tEXt --out-file-path assests/newdice.png
+///cargo run --release -- remove --in-file-path assests/dice.png --chunk-type tEXt --in-place --all
+fn execute_remove(remove_args: RemoveArgs) -> Result<(), PngError> {
+    if remove_args.in_place && remove_args.in_file_path == Path::new("-") {
+        return Err(PngError::InvalidArgument(
+            "--in-place needs a file, not stdin".to_string(),
+        ));
+    }
+    let mut png_image = Png::read_from(open_input(&remove_args.in_file_path)?)?;
+
+    let chunks = if remove_args.all {
+        png_image.remove_all_chunks(remove_args.chunk_type.as_str())?
+    } else {
+        vec![png_image.remove_first_chunk(remove_args.chunk_type.as_str())?]
+    };
+    for chunk in &chunks {
+        println!("Chunk removed: {:?}", chunk);
+    }
+
+    match remove_args.out_file_path {
+        Some(out_file_path) => {
+            let mut out_file = BufWriter::new(File::create(out_file_path)?);
+            png_image.write_to(&mut out_file)
+        }
+        None => write_atomically(&png_image, &remove_args.in_file_path),
+    }
+}
+
 /// cargo run --release -- print --in-file-path assests/dice.png
-fn execute_print(print_args: PrintArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read(print_args.in_file_path)?;
-    let png_image = Png::try_from(&file[..]).unwrap();
-    for chunk in png_image.chunks() {
+fn execute_print(print_args: PrintArgs) -> Result<(), PngError> {
+    let mut png_reader = PngReader::new(open_input(&print_args.in_file_path)?)?;
+    while let Some(chunk) = png_reader.read_chunk()? {
         println!("{:?}", chunk.data());
     }
+    let mut trailing_data = Vec::new();
+    png_reader.into_inner().read_to_end(&mut trailing_data)?;
+    if !trailing_data.is_empty() {
+        println!(
+            "{} bytes after IEND: {:?}",
+            trailing_data.len(),
+            trailing_data
+        );
+    }
+    Ok(())
+}
+
+/// cargo run --release -- info --in-file-path assests/dice.png
+fn execute_info(info_args: InfoArgs) -> Result<(), PngError> {
+    let mut png_reader = PngReader::new(open_input(&info_args.in_file_path)?)?;
+    let first_chunk = png_reader
+        .read_chunk()?
+        .ok_or_else(|| PngError::ChunkNotFound("IHDR".to_string()))?;
+    let ihdr = Ihdr::try_from(&first_chunk)?;
+
+    // Chunk types in order of first appearance, with their count and total data length
+    let mut chunk_counts: Vec<(ChunkType, usize, usize)> = Vec::new();
+    let mut file_size = Png::STANDARD_HEADER.len();
+    for chunk in std::iter::once(Ok(first_chunk)).chain(png_reader.by_ref()) {
+        let chunk = chunk?;
+        file_size += chunk.data().len() + Chunk::METADATA_LENGTH;
+        match chunk_counts
+            .iter_mut()
+            .find(|(chunk_type, _, _)| chunk_type == chunk.chunk_type())
+        {
+            Some((_, count, length)) => {
+                *count += 1;
+                *length += chunk.data().len();
+            }
+            None => chunk_counts.push((*chunk.chunk_type(), 1, chunk.data().len())),
+        }
+    }
+    let mut trailing_data = Vec::new();
+    png_reader.into_inner().read_to_end(&mut trailing_data)?;
+    file_size += trailing_data.len();
+
+    println!("File size: {} bytes", file_size);
+    println!("Dimensions: {} x {}", ihdr.width, ihdr.height);
+    println!("Bit depth: {}", ihdr.bit_depth);
+    println!(
+        "Color type: {} ({})",
+        ihdr.color_type,
+        ihdr.color_type.to_byte()
+    );
+    println!("Compression method: {} (deflate)", ihdr.compression_method);
+    println!("Filter method: {} (adaptive)", ihdr.filter_method);
+    println!("Interlace: {}", ihdr.interlace);
+    println!("Chunks:");
+    for (chunk_type, count, length) in chunk_counts {
+        println!("  {} x{} ({} bytes)", chunk_type, count, length);
+    }
+    if !trailing_data.is_empty() {
+        println!("Trailing data: {} bytes after IEND", trailing_data.len());
+    }
     Ok(())
 }
+
+/// cargo run --release -- analyze --in-file-path assests/dice.png --in-file-path assests/newdice.png
+fn execute_analyze(analyze_args: AnalyzeArgs) -> Result<(), PngError> {
+    // Every file gets a report even when one can't be read; the first error is returned at the end
+    let mut first_error = None;
+    for in_file_path in &analyze_args.in_file_paths {
+        let mut file = Vec::new();
+        let report = open_input(in_file_path)
+            .and_then(|mut input| Ok(input.read_to_end(&mut file)?))
+            .and_then(|_| analysis::analyze(&file));
+        let report = match report {
+            Ok(report) => report,
+            Err(err) => {
+                eprintln!("{}: {}", in_file_path.display(), err);
+                first_error.get_or_insert(err);
+                continue;
+            }
+        };
+
+        match report.suspicion() {
+            Some(severity) => println!("{}: {} suspicion", in_file_path.display(), severity),
+            None => println!("{}: nothing suspicious", in_file_path.display()),
+        }
+        for finding in &report.findings {
+            println!("  [{}] {}", finding.severity, finding.description);
+        }
+        match &report.lsb_statistics {
+            Some(statistics) => println!(
+                "  LSBs of {} color samples: chi-square p = {}, equalized over the first {:.0}%, RS embedding rate {}",
+                statistics.samples,
+                statistics
+                    .chi_square_p_value
+                    .map_or("n/a".to_string(), |p_value| format!("{:.3}", p_value)),
+                statistics.equalized_prefix * 100.0,
+                statistics
+                    .rs_embedding_rate
+                    .map_or("n/a".to_string(), |rate| format!("{:.0}%", rate * 100.0)),
+            ),
+            None => println!("  LSB tests don't apply to indexed or low bit depth images"),
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// cargo run --release -- capacity --in-file-path assests/dice.png
+/// cargo run --release -- capacity --in-file-path assests/dice.png --payload-file notes.pdf --compress zstd --encryption passphrase --sign
+fn execute_capacity(capacity_args: CapacityArgs) -> Result<(), PngError> {
+    let png_image = Png::read_from(open_input(&capacity_args.in_file_path)?)?;
+    let ihdr = png_image.ihdr()?;
+    let payload = match &capacity_args.payload_file {
+        Some(payload_file) => Some((
+            FilePayload::from_path(payload_file, Vec::new()).header_length(),
+            std::fs::metadata(payload_file)?.len() as usize,
+        )),
+        None => None,
+    };
+    let framing = Framing {
+        file_header_length: payload.map_or(0, |(header_length, _)| header_length),
+        compression: codec_for(capacity_args.compress),
+        encryption: match capacity_args.encryption {
+            Encryption::None => capacity::Encryption::None,
+            Encryption::Passphrase => capacity::Encryption::Passphrase,
+            Encryption::Recipients => {
+                capacity::Encryption::Recipients(capacity_args.recipient_count as usize)
+            }
+        },
+        signed: capacity_args.sign,
+        raw: capacity_args.raw,
+    };
+    let max_chunk_size = capacity_args.max_chunk_size as usize;
+    let estimate = capacity::estimate(
+        &ihdr,
+        &framing,
+        max_chunk_size,
+        &lsb_options(Method::Lsb, None, Some(capacity_args.matrix_bits))?,
+    )?;
+
+    println!(
+        "{}: {}x{}, framing adds {} bytes",
+        capacity_args.in_file_path.display(),
+        ihdr.width,
+        ihdr.height,
+        estimate.overhead
+    );
+    // Length of the payload file's data and of the message it would make
+    let lengths =
+        payload.map(|(_, data_length)| (data_length, framing.message_length(data_length)));
+    if let (Some(payload_file), Some((data_length, message_length))) =
+        (&capacity_args.payload_file, lengths)
+    {
+        println!(
+            "{}: {} bytes, at most {} once framed",
+            payload_file.display(),
+            data_length,
+            message_length
+        );
+    }
+    let verdict = |fits: bool| match lengths {
+        Some(_) if fits => " - fits",
+        Some(_) => " - too large",
+        None => "",
+    };
+    let bytes_or_nothing = |content_length: Option<usize>| {
+        content_length.map_or("no room for the framing".to_string(), |length| {
+            format!("{} bytes", length)
+        })
+    };
+
+    let chunks_needed = lengths
+        .and_then(|(_, message_length)| fragment::piece_count(message_length, max_chunk_size));
+    println!(
+        "  ancillary chunk: {} in one chunk, {}{}",
+        bytes_or_nothing(estimate.chunk_content_length),
+        estimate.fragment_data_length.map_or(
+            "no room for fragment headers".to_string(),
+            |length| format!("unlimited across chunks of {} message bytes each", length)
+        ),
+        match (lengths, chunks_needed) {
+            (None, _) => String::new(),
+            (Some(_), Some(1)) => " - fits in 1 chunk".to_string(),
+            (Some(_), Some(count)) => format!(" - fits in {} chunks", count),
+            (Some(_), None) => verdict(false).to_string(),
+        }
+    );
+    if estimate.lsb.is_empty() {
+        println!("  LSB: indexed and low bit depth images can't carry LSB data");
+    }
+    for lsb_estimate in &estimate.lsb {
+        println!(
+            "  LSB, {}: {}{}",
+            // `encode` writes one bit per sample, more is only available through the library
+            if lsb_estimate.bits_per_sample == 1 {
+                "1 bit per sample".to_string()
+            } else {
+                format!(
+                    "{} bits per sample (library only)",
+                    lsb_estimate.bits_per_sample
+                )
+            },
+            bytes_or_nothing(lsb_estimate.content_length),
+            verdict(lengths.is_some_and(|(data_length, _)| {
+                lsb_estimate
+                    .content_length
+                    .is_some_and(|content_length| data_length <= content_length)
+            }))
+        );
+    }
+    println!(
+        "  trailing data: unlimited, {} bytes of framing with the passphrase encryption it requires{}",
+        estimate.trailer_overhead,
+        verdict(true)
+    );
+    Ok(())
+}
+
+/// cargo run --release -- verify --in-file-path assests/newdice.png --chunk-type ruSt --trusted-key <public key>
+fn execute_verify(verify_args: VerifyArgs) -> Result<(), PngError> {
+    let chunk_type = ChunkType::from_str(&verify_args.chunk_type)?;
+    let png_image = Png::read_from(open_input(&verify_args.in_file_path)?)?;
+
+    let mut trusted_keys = verify_args
+        .trusted_keys
+        .iter()
+        .map(|key| VerifyingKey::from_hex(key))
+        .collect::<Result<Vec<VerifyingKey>, PngError>>()?;
+    if let Some(trusted_keys_file) = &verify_args.trusted_keys_file {
+        for line in std::fs::read_to_string(trusted_keys_file)?.lines() {
+            let key = line.split('#').next().unwrap_or_default().trim();
+            if !key.is_empty() {
+                trusted_keys.push(VerifyingKey::from_hex(key)?);
+            }
+        }
+    }
+
+    let image = image_bytes_for_signature(&png_image, &chunk_type);
+    let messages = fragment::reassemble(
+        png_image
+            .chunks_by_type(&chunk_type.to_string())
+            .map(Chunk::data),
+    )?;
+    if messages.is_empty() {
+        return Err(PngError::ChunkNotFound(chunk_type.to_string()));
+    }
+    for message in &messages {
+        let signed_data = if container::is_container(message) {
+            Container::parse(message)?.content()
+        } else {
+            &message[..]
+        };
+        let signed_payload = SignedPayload::parse(signed_data)?;
+        signed_payload.verify(&trusted_keys, Some(&image))?;
+        println!(
+            "Valid signature by {}{}",
+            signed_payload.signer().to_hex(),
+            if signed_payload.covers_image() {
+                " (covers the image)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Critical chunks that an image signature covers. Chunks of the message's own type are
+/// left out, since they are added after signing.
+fn image_bytes_for_signature(png_image: &Png, message_chunk_type: &ChunkType) -> Vec<u8> {
+    Png::from_chunks(
+        png_image
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type() != message_chunk_type)
+            .cloned()
+            .collect(),
+    )
+    .critical_canonical_bytes()
+}
+
+/// cargo run --release -- keygen --out-file-path assests/key
+fn execute_keygen(keygen_args: KeygenArgs) -> Result<(), PngError> {
+    let (private_key, public_key) = if keygen_args.signing {
+        let signing_key = SigningKey::generate();
+        (signing_key.to_hex(), signing_key.verifying_key().to_hex())
+    } else {
+        let identity = Identity::generate();
+        (identity.to_hex(), identity.recipient().to_hex())
+    };
+
+    write_private_key(&keygen_args.out_file_path, &private_key)?;
+    let mut public_key_path = keygen_args.out_file_path.into_os_string();
+    public_key_path.push(".pub");
+    std::fs::write(&public_key_path, format!("{}\n", public_key))?;
+
+    println!("Public key: {}", public_key);
+    Ok(())
+}
+
+/// Creates a new file readable only by the current user; never overwrites an existing key
+fn write_private_key(path: &Path, key: &str) -> Result<(), PngError> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut key_file = options.open(path)?;
+    key_file.write_all(format!("{}\n", key).as_bytes())?;
+    Ok(())
+}
+
+/// Writes `png_image` to a temporary file next to `path` and renames it over `path`,
+/// so readers see either the old or the new file but never a partial one. The file keeps
+/// the permissions of the one it replaces.
+fn write_atomically(png_image: &Png, path: &Path) -> Result<(), PngError> {
+    let permissions = std::fs::metadata(path)?.permissions();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| PngError::InvalidArgument("path has no file name".to_string()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".pngme-{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut temp_file = BufWriter::new(File::create(&temp_path)?);
+        png_image.write_to(&mut temp_file)?;
+        temp_file.flush()?;
+        temp_file.get_ref().set_permissions(permissions)?;
+        temp_file.get_ref().sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Opens the input PNG for streaming, `-` reads from stdin
+fn open_input(path: &Path) -> Result<Box<dyn Read>, PngError> {
+    if path == Path::new("-") {
+        return Ok(Box::new(BufReader::new(std::io::stdin())));
+    }
+    Ok(Box::new(BufReader::new(File::open(path)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    fn run(args: &[&str]) -> Result<ExitCode, PngError> {
+        let args = Args::try_parse_from(std::iter::once("pngme_bin").chain(args.iter().copied()))
+            .unwrap_or_else(|err| panic!("{}", err));
+        execute_command(args.commands)
+    }
+
+    /// A copy of a PngSuite image in a directory of its own, named after the test
+    fn scratch_png(test_name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("pngme-{}-{}", std::process::id(), test_name));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("image.png");
+        std::fs::copy(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/pngsuite/basn2c08.png"),
+            &path,
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_missing_chunk_exit_code() {
+        let png = scratch_png("missing_chunk");
+        let png = png.to_str().unwrap();
+
+        let exit_code = run(&["decode", "-i", png, "--chunk-type", "ruSt"]).unwrap();
+        assert_eq!(exit_code, ExitCode::from(EXIT_CHUNK_NOT_FOUND));
+        // Other commands report missing chunks as ordinary errors
+        assert!(matches!(
+            run(&["remove", "-i", png, "--chunk-type", "ruSt", "--in-place"]),
+            Err(PngError::ChunkNotFound(_))
+        ));
+
+        run(&[
+            "encode",
+            "-i",
+            png,
+            "--chunk-type",
+            "ruSt",
+            "--message",
+            "Hello",
+            "-o",
+            png,
+        ])
+        .unwrap();
+        let exit_code = run(&["decode", "-i", png, "--chunk-type", "ruSt"]).unwrap();
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_in_place_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let png = scratch_png("in_place_permissions");
+        let png = png.to_str().unwrap();
+        run(&[
+            "encode",
+            "-i",
+            png,
+            "--chunk-type",
+            "ruSt",
+            "--message",
+            "Hello",
+            "-o",
+            png,
+        ])
+        .unwrap();
+        std::fs::set_permissions(png, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        run(&["remove", "-i", png, "--chunk-type", "ruSt", "--in-place"]).unwrap();
+        let mode = std::fs::metadata(png).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert!(matches!(
+            run(&["remove", "-i", png, "--chunk-type", "ruSt", "--in-place"]),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_type_only_applies_to_chunk_method() {
+        let png = scratch_png("chunk_type_method");
+        let png = png.to_str().unwrap();
+        let output = png.replace("image.png", "output.png");
+
+        for method in ["lsb", "trailer"] {
+            let encoded = run(&[
+                "encode",
+                "-i",
+                png,
+                "--method",
+                method,
+                "--chunk-type",
+                "ruSt",
+                "--message",
+                "Hello",
+                "--passphrase",
+                "hunter2",
+                "-o",
+                &output,
+            ]);
+            assert!(
+                matches!(encoded, Err(PngError::InvalidArgument(_))),
+                "{}: {:?}",
+                method,
+                encoded
+            );
+            let decoded = run(&[
+                "decode",
+                "-i",
+                png,
+                "--method",
+                method,
+                "--chunk-type",
+                "ruSt",
+                "--passphrase",
+                "hunter2",
+            ]);
+            assert!(
+                matches!(decoded, Err(PngError::InvalidArgument(_))),
+                "{}: {:?}",
+                method,
+                decoded
+            );
+        }
+        assert!(!Path::new(&output).exists());
+    }
+}
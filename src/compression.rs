@@ -0,0 +1,183 @@
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+
+use crate::error::{PngError, Result};
+
+// Compressed data layout:
+// codec (1) | compressed data
+const CODEC_ZLIB: u8 = 1;
+const CODEC_ZSTD: u8 = 2;
+
+/// Upper bound on the size of decompressed data unless the caller asks for another one, 256 MiB
+pub const DEFAULT_MAX_DECOMPRESSED_LENGTH: usize = 256 * 1024 * 1024;
+
+/// Compression algorithm applied to a message before it is encrypted and embedded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Zlib,
+    Zstd,
+}
+
+impl Codec {
+    fn to_byte(self) -> u8 {
+        match self {
+            Codec::Zlib => CODEC_ZLIB,
+            Codec::Zstd => CODEC_ZSTD,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Codec> {
+        match byte {
+            CODEC_ZLIB => Ok(Codec::Zlib),
+            CODEC_ZSTD => Ok(Codec::Zstd),
+            _ => Err(PngError::MalformedPayload(format!(
+                "unknown compression codec {}",
+                byte
+            ))),
+        }
+    }
+}
+
+/// Compresses `data` with `codec`, prefixed by a byte recording the codec
+pub fn compress(data: &[u8], codec: Codec) -> Result<Vec<u8>> {
+    let mut compressed = vec![codec.to_byte()];
+    match codec {
+        Codec::Zlib => compressed.extend_from_slice(&zlib_compress(data)?),
+        Codec::Zstd => {
+            let mut encoder = zstd::Encoder::new(compressed, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            encoder.write_all(data)?;
+            compressed = encoder.finish()?;
+        }
+    }
+    Ok(compressed)
+}
+
+/// Largest output `compress` can produce for `length` bytes of input, reached when the data
+/// doesn't compress at all. These are the bounds zlib's `compressBound` and zstd's
+/// `ZSTD_compressBound` guarantee, plus the codec byte.
+pub fn max_compressed_length(length: usize, codec: Codec) -> usize {
+    let bound = match codec {
+        Codec::Zlib => length + (length >> 12) + (length >> 14) + (length >> 25) + 13,
+        Codec::Zstd => {
+            const SMALL_DATA_LIMIT: usize = 128 * 1024;
+            let margin = SMALL_DATA_LIMIT.saturating_sub(length) >> 11;
+            length + (length >> 8) + margin
+        }
+    };
+    1 + bound
+}
+
+/// Reverses `compress`. Fails with `PngError::ExpansionLimitExceeded` instead of allocating
+/// more than `max_length` bytes, so a small crafted chunk can't exhaust memory.
+pub fn decompress(data: &[u8], max_length: usize) -> Result<Vec<u8>> {
+    let (&codec, compressed) = data.split_first().ok_or(PngError::Truncated {
+        needed: 1,
+        available: 0,
+    })?;
+    match Codec::from_byte(codec)? {
+        Codec::Zlib => zlib_decompress(compressed, max_length),
+        Codec::Zstd => read_limited(
+            zstd::Decoder::with_buffer(compressed).map_err(invalid_data)?,
+            max_length,
+        ),
+    }
+}
+
+/// Compresses `data` into a bare zlib stream, as PNG uses for image and text data
+pub(crate) fn zlib_compress(data: &[u8]) -> Result<Vec<u8>> {
+    zlib_compress_with_level(data, flate2::Compression::default().level())
+}
+
+/// Like `zlib_compress`, with a compression level from 0 (store only) to 9 (smallest output)
+pub(crate) fn zlib_compress_with_level(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Inflates a bare zlib stream, with the same limit as `decompress`
+pub(crate) fn zlib_decompress(data: &[u8], max_length: usize) -> Result<Vec<u8>> {
+    read_limited(ZlibDecoder::new(data), max_length)
+}
+
+fn read_limited(decoder: impl Read, max_length: usize) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    // One byte past the limit tells a stream that exactly fills it from one that overflows
+    decoder
+        .take(max_length as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(invalid_data)?;
+    if decompressed.len() > max_length {
+        return Err(PngError::ExpansionLimitExceeded(max_length));
+    }
+    Ok(decompressed)
+}
+
+fn invalid_data(err: std::io::Error) -> PngError {
+    PngError::MalformedPayload(format!("compressed data is corrupt: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    const CODECS: [Codec; 2] = [Codec::Zlib, Codec::Zstd];
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"a highly repetitive message ".repeat(100);
+        for codec in CODECS {
+            let compressed = compress(&data, codec).unwrap();
+            assert!(compressed.len() < data.len() / 10);
+            assert_eq!(
+                decompress(&compressed, DEFAULT_MAX_DECOMPRESSED_LENGTH).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_compressed_length() {
+        let noise = test_util::noise(0x9e37_79b9, 300_000);
+        for codec in CODECS {
+            for length in [0, 1, 1000, 200_000, 300_000] {
+                let compressed = compress(&noise[..length], codec).unwrap();
+                assert!(compressed.len() > length);
+                assert!(compressed.len() <= max_compressed_length(length, codec));
+            }
+        }
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        let data = vec![0; 10_000];
+        for codec in CODECS {
+            let compressed = compress(&data, codec).unwrap();
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+            assert!(matches!(
+                decompress(&compressed, data.len() - 1),
+                Err(PngError::ExpansionLimitExceeded(9_999))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_data() {
+        for codec in CODECS {
+            let mut compressed = compress(b"secret message", codec).unwrap();
+            compressed.truncate(compressed.len() / 2);
+            assert!(matches!(
+                decompress(&compressed, DEFAULT_MAX_DECOMPRESSED_LENGTH),
+                Err(PngError::MalformedPayload(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unknown_codec() {
+        assert!(decompress(&[0, 1, 2, 3], DEFAULT_MAX_DECOMPRESSED_LENGTH).is_err());
+        assert!(decompress(&[], DEFAULT_MAX_DECOMPRESSED_LENGTH).is_err());
+    }
+}
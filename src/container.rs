@@ -0,0 +1,209 @@
+use crate::{
+    byte_reader::ByteReader,
+    chunk::ALGORITHM_CRC,
+    error::{PngError, Result},
+};
+
+// Container layout:
+// magic (4) | version (1) | flags (1) | content length (4) | content CRC-32 (4) | content
+// The flags describe how the content was produced, from the outermost layer inwards:
+// signed (see `signing`), encrypted (see `crypto`), compressed, and finally either the raw
+// message or a file payload (see `payload`). Unknown flags are rejected, so an older reader
+// fails clearly on a container that uses a newer feature.
+// Fragmentation (see `fragment`) is not a flag: messages are split after they are wrapped, so
+// each fragment carries part of a container and the container is only parsed once reassembled.
+const MAGIC: [u8; 4] = *b"PMCT";
+const VERSION: u8 = 1;
+const FLAG_COMPRESSED: u8 = 1;
+const FLAG_ENCRYPTED: u8 = 1 << 1;
+const FLAG_SIGNED: u8 = 1 << 2;
+const FLAG_FILE: u8 = 1 << 3;
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_ENCRYPTED | FLAG_SIGNED | FLAG_FILE;
+/// Bytes taken by the container header in front of the content
+pub const HEADER_LENGTH: usize = 14;
+
+/// Layers that were applied to the content of a container.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    pub compressed: bool,
+    pub encrypted: bool,
+    pub signed: bool,
+    /// The innermost data is a `payload::FilePayload` rather than the raw message
+    pub file: bool,
+}
+
+impl Flags {
+    fn to_byte(self) -> u8 {
+        [
+            (self.compressed, FLAG_COMPRESSED),
+            (self.encrypted, FLAG_ENCRYPTED),
+            (self.signed, FLAG_SIGNED),
+            (self.file, FLAG_FILE),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |byte, (_, flag)| byte | flag)
+    }
+
+    fn from_byte(byte: u8) -> Result<Flags> {
+        if byte & !KNOWN_FLAGS != 0 {
+            return Err(PngError::MalformedPayload(format!(
+                "unknown container flags {:#04x}",
+                byte & !KNOWN_FLAGS
+            )));
+        }
+        Ok(Flags {
+            compressed: byte & FLAG_COMPRESSED != 0,
+            encrypted: byte & FLAG_ENCRYPTED != 0,
+            signed: byte & FLAG_SIGNED != 0,
+            file: byte & FLAG_FILE != 0,
+        })
+    }
+}
+
+/// Returns true if `data` starts with the header written by `wrap`
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Frames `content` with a header recording its `flags`, length and checksum
+pub fn wrap(content: &[u8], flags: Flags) -> Result<Vec<u8>> {
+    let length = u32::try_from(content.len()).map_err(|_| {
+        PngError::MalformedPayload("content is too long for a container".to_string())
+    })?;
+    let crc = crc::Crc::<u32>::new(&ALGORITHM_CRC).checksum(content);
+
+    let mut container = Vec::with_capacity(HEADER_LENGTH + content.len());
+    container.extend_from_slice(&MAGIC);
+    container.push(VERSION);
+    container.push(flags.to_byte());
+    container.extend_from_slice(&length.to_be_bytes());
+    container.extend_from_slice(&crc.to_be_bytes());
+    container.extend_from_slice(content);
+    Ok(container)
+}
+
+/// A container written by `wrap`, with its length and checksum already checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Container<'a> {
+    flags: Flags,
+    content: &'a [u8],
+}
+
+impl<'a> Container<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Container<'a>> {
+        let mut reader = ByteReader::new(data);
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(PngError::MalformedPayload(
+                "data is not a container".to_string(),
+            ));
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(PngError::MalformedPayload(format!(
+                "unsupported container version {}",
+                version
+            )));
+        }
+        let flags = Flags::from_byte(reader.read_u8()?)?;
+        let length = reader.read_u32()? as usize;
+        let stored_crc = reader.read_u32()?;
+        let content = reader.rest();
+        if content.len() != length {
+            return Err(PngError::MalformedPayload(format!(
+                "container declares {} bytes of content but holds {}",
+                length,
+                content.len()
+            )));
+        }
+        let computed_crc = crc::Crc::<u32>::new(&ALGORITHM_CRC).checksum(content);
+        if computed_crc != stored_crc {
+            return Err(PngError::PayloadChecksumMismatch {
+                expected: computed_crc,
+                actual: stored_crc,
+            });
+        }
+
+        Ok(Container { flags, content })
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    pub fn content(&self) -> &'a [u8] {
+        self.content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_and_parse() {
+        let flags = Flags {
+            encrypted: true,
+            file: true,
+            ..Flags::default()
+        };
+        let wrapped = wrap(b"secret message", flags).unwrap();
+
+        assert!(is_container(&wrapped));
+        assert_eq!(wrapped.len(), HEADER_LENGTH + 14);
+        let container = Container::parse(&wrapped).unwrap();
+        assert_eq!(container.flags(), flags);
+        assert_eq!(container.content(), b"secret message");
+    }
+
+    #[test]
+    fn test_flags_round_trip() {
+        for byte in 0..=KNOWN_FLAGS {
+            assert_eq!(Flags::from_byte(byte).unwrap().to_byte(), byte);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_content() {
+        let mut wrapped = wrap(b"secret message", Flags::default()).unwrap();
+        let last = wrapped.len() - 1;
+        wrapped[last] ^= 1;
+
+        assert!(matches!(
+            Container::parse(&wrapped),
+            Err(PngError::PayloadChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_length_mismatch() {
+        let mut wrapped = wrap(b"secret message", Flags::default()).unwrap();
+        wrapped.push(0);
+
+        assert!(matches!(
+            Container::parse(&wrapped),
+            Err(PngError::MalformedPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_flags_and_version() {
+        let wrapped = wrap(b"secret message", Flags::default()).unwrap();
+
+        let mut newer_flags = wrapped.clone();
+        newer_flags[5] = 0x80;
+        assert!(Container::parse(&newer_flags).is_err());
+
+        let mut newer_version = wrapped;
+        newer_version[4] = VERSION + 1;
+        assert!(Container::parse(&newer_version).is_err());
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let wrapped = wrap(b"secret message", Flags::default()).unwrap();
+        for length in 0..HEADER_LENGTH {
+            assert!(Container::parse(&wrapped[..length]).is_err());
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce, XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    byte_reader::ByteReader,
+    error::{PngError, Result},
+};
+
+// Encrypted data layout (big-endian):
+// magic (4) | version (1) | scheme (1) | scheme header | nonce (24) | ciphertext + tag (16)
+// The passphrase scheme header is: memory KiB (4) | iterations (4) | parallelism (4) | salt length (1) | salt
+// The recipients scheme header is: ephemeral public key (32) | recipient count (2) | wrapped key (48) per recipient
+// Everything before the ciphertext is authenticated as associated data, so tampering with
+// the header fails decryption just like tampering with the ciphertext.
+const MAGIC: [u8; 4] = *b"PMEC";
+const VERSION: u8 = 1;
+const SCHEME_PASSPHRASE: u8 = 1;
+const SCHEME_RECIPIENTS: u8 = 2;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+const TAG_LENGTH: usize = 16;
+const WRAPPED_KEY_LENGTH: usize = KEY_LENGTH + TAG_LENGTH;
+const WRAP_KEY_INFO: &[u8] = b"pngme x25519 key wrap";
+// Magic, version and scheme
+const PREFIX_LENGTH: usize = 6;
+// Three KDF parameters, salt length and salt
+const PASSPHRASE_HEADER_LENGTH: usize = 12 + 1 + SALT_LENGTH;
+// Ephemeral public key and recipient count, followed by the wrapped keys
+const RECIPIENTS_HEADER_LENGTH: usize = KEY_LENGTH + 2;
+
+/// Argon2id cost parameters used to stretch a passphrase into an encryption key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    // Upper bounds accepted when decrypting, four times the defaults we encrypt with, so a crafted
+    // header can't make decoding take gigabytes of memory or minutes of hashing
+    pub const MAX_MEMORY_KIB: u32 = 4 * Params::DEFAULT_M_COST;
+    pub const MAX_ITERATIONS: u32 = 4 * Params::DEFAULT_T_COST;
+    pub const MAX_PARALLELISM: u32 = 4 * Params::DEFAULT_P_COST;
+
+    fn to_argon2(self) -> Result<Argon2<'static>> {
+        if self.memory_kib > KdfParams::MAX_MEMORY_KIB
+            || self.iterations > KdfParams::MAX_ITERATIONS
+            || self.parallelism > KdfParams::MAX_PARALLELISM
+        {
+            return Err(PngError::MalformedPayload(format!(
+                "KDF parameters {:?} exceed the allowed limits",
+                self
+            )));
+        }
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|err| PngError::MalformedPayload(format!("invalid KDF parameters: {}", err)))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Returns true if `data` starts with the header written by the encrypt functions
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Bytes `encrypt_with_passphrase` adds to the plaintext
+pub const PASSPHRASE_OVERHEAD: usize =
+    PREFIX_LENGTH + PASSPHRASE_HEADER_LENGTH + NONCE_LENGTH + TAG_LENGTH;
+
+/// Bytes `encrypt_to_recipients` adds to the plaintext for `count` recipients
+pub fn recipients_overhead(count: usize) -> usize {
+    PREFIX_LENGTH
+        + RECIPIENTS_HEADER_LENGTH
+        + count * WRAPPED_KEY_LENGTH
+        + NONCE_LENGTH
+        + TAG_LENGTH
+}
+
+/// Encrypts `plaintext` with XChaCha20-Poly1305 under a key derived from `passphrase` with Argon2id.
+pub fn encrypt_with_passphrase(
+    plaintext: &[u8],
+    passphrase: &str,
+    kdf_params: KdfParams,
+) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, kdf_params)?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(SCHEME_PASSPHRASE);
+    header.extend_from_slice(&kdf_params.memory_kib.to_be_bytes());
+    header.extend_from_slice(&kdf_params.iterations.to_be_bytes());
+    header.extend_from_slice(&kdf_params.parallelism.to_be_bytes());
+    header.push(SALT_LENGTH as u8);
+    header.extend_from_slice(&salt);
+
+    seal(&key, header, plaintext)
+}
+
+/// Reverses `encrypt_with_passphrase`. A wrong passphrase or modified data gives `PngError::DecryptionFailed`.
+pub fn decrypt_with_passphrase(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut reader = ByteReader::new(data);
+    let scheme = read_preamble(&mut reader)?;
+    if scheme != SCHEME_PASSPHRASE {
+        return Err(PngError::MalformedPayload(format!(
+            "data is not encrypted with a passphrase (scheme {})",
+            scheme
+        )));
+    }
+    let kdf_params = KdfParams {
+        memory_kib: reader.read_u32()?,
+        iterations: reader.read_u32()?,
+        parallelism: reader.read_u32()?,
+    };
+    let salt_length = reader.read_u8()? as usize;
+    let salt = reader.read_bytes(salt_length)?;
+    let key = derive_key(passphrase, salt, kdf_params)?;
+
+    let header_length = data.len() - reader.rest().len();
+    open(&key, data, header_length)
+}
+
+/// X25519 private key that can decrypt messages sent to its `Recipient`.
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub fn generate() -> Identity {
+        Identity(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_bytes())
+    }
+
+    pub fn from_hex(key: &str) -> Result<Identity> {
+        Ok(Identity(StaticSecret::from(key_from_hex(key)?)))
+    }
+}
+
+/// X25519 public key that messages can be encrypted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Recipient {
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_bytes())
+    }
+
+    pub fn from_hex(key: &str) -> Result<Recipient> {
+        Ok(Recipient(PublicKey::from(key_from_hex(key)?)))
+    }
+}
+
+/// Parses a 32-byte key written as hex, which X25519 and Ed25519 keys share
+pub(crate) fn key_from_hex(key: &str) -> Result<[u8; KEY_LENGTH]> {
+    let mut bytes = [0; KEY_LENGTH];
+    hex::decode_to_slice(key.trim(), &mut bytes)
+        .map_err(|err| PngError::InvalidKey(err.to_string()))?;
+    Ok(bytes)
+}
+
+/// Encrypts `plaintext` so that any one of `recipients` can decrypt it with their `Identity`.
+///
+/// A random file key encrypts the message with XChaCha20-Poly1305. It is wrapped once per recipient
+/// under a key derived with HKDF-SHA256 from an X25519 exchange with a fresh ephemeral key.
+pub fn encrypt_to_recipients(plaintext: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>> {
+    if recipients.is_empty() || recipients.len() > u16::MAX as usize {
+        return Err(PngError::InvalidKey(format!(
+            "between 1 and {} recipients are needed, got {}",
+            u16::MAX,
+            recipients.len()
+        )));
+    }
+    let mut file_key = [0; KEY_LENGTH];
+    OsRng.fill_bytes(&mut file_key);
+    // One ephemeral key per message is shared by all recipients, so it must be reusable
+    let ephemeral_secret = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral_secret);
+
+    let mut wrapped_keys = Vec::with_capacity(recipients.len() * WRAPPED_KEY_LENGTH);
+    for recipient in recipients {
+        let shared_secret = ephemeral_secret.diffie_hellman(&recipient.0);
+        if !shared_secret.was_contributory() {
+            return Err(PngError::InvalidKey(
+                "recipient public key is a low-order point".to_string(),
+            ));
+        }
+        let wrap_key = derive_wrap_key(shared_secret.as_bytes(), &ephemeral_public, &recipient.0);
+        let wrapped_key = ChaCha20Poly1305::new((&wrap_key).into())
+            .encrypt(&Nonce::default(), &file_key[..])
+            .map_err(|_| PngError::MalformedPayload("key wrapping failed".to_string()))?;
+        wrapped_keys.extend_from_slice(&wrapped_key);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(SCHEME_RECIPIENTS);
+    header.extend_from_slice(ephemeral_public.as_bytes());
+    header.extend_from_slice(&(recipients.len() as u16).to_be_bytes());
+    header.extend_from_slice(&wrapped_keys);
+
+    seal(&file_key, header, plaintext)
+}
+
+/// Reverses `encrypt_to_recipients`. If `identity` is not one of the recipients,
+/// or the data was modified, this gives `PngError::DecryptionFailed`.
+pub fn decrypt_with_identity(data: &[u8], identity: &Identity) -> Result<Vec<u8>> {
+    let mut reader = ByteReader::new(data);
+    let scheme = read_preamble(&mut reader)?;
+    if scheme != SCHEME_RECIPIENTS {
+        return Err(PngError::MalformedPayload(format!(
+            "data is not encrypted to recipients (scheme {})",
+            scheme
+        )));
+    }
+    let ephemeral_public = PublicKey::from(reader.read_array::<KEY_LENGTH>()?);
+    let recipient_count = u16::from_be_bytes(reader.read_array()?) as usize;
+    let wrapped_keys = reader.read_bytes(recipient_count * WRAPPED_KEY_LENGTH)?;
+
+    let shared_secret = identity.0.diffie_hellman(&ephemeral_public);
+    let wrap_key = derive_wrap_key(
+        shared_secret.as_bytes(),
+        &ephemeral_public,
+        &identity.recipient().0,
+    );
+    let wrap_cipher = ChaCha20Poly1305::new((&wrap_key).into());
+    // Recipients are not named in the header, so every wrapped key is tried
+    let file_key = wrapped_keys
+        .chunks(WRAPPED_KEY_LENGTH)
+        .find_map(|wrapped_key| wrap_cipher.decrypt(&Nonce::default(), wrapped_key).ok())
+        .ok_or(PngError::DecryptionFailed)?;
+    let file_key: [u8; KEY_LENGTH] = file_key
+        .try_into()
+        .map_err(|_| PngError::DecryptionFailed)?;
+
+    let header_length = data.len() - reader.rest().len();
+    open(&file_key, data, header_length)
+}
+
+fn derive_wrap_key(
+    shared_secret: &[u8; KEY_LENGTH],
+    ephemeral_public: &PublicKey,
+    recipient: &PublicKey,
+) -> [u8; KEY_LENGTH] {
+    let mut salt = [0; 2 * KEY_LENGTH];
+    salt[..KEY_LENGTH].copy_from_slice(ephemeral_public.as_bytes());
+    salt[KEY_LENGTH..].copy_from_slice(recipient.as_bytes());
+    let mut wrap_key = [0; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(WRAP_KEY_INFO, &mut wrap_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    wrap_key
+}
+
+fn read_preamble(reader: &mut ByteReader) -> Result<u8> {
+    if reader.read_array::<4>()? != MAGIC {
+        return Err(PngError::MalformedPayload(
+            "data is not encrypted".to_string(),
+        ));
+    }
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(PngError::MalformedPayload(format!(
+            "unsupported encryption version {}",
+            version
+        )));
+    }
+    reader.read_u8()
+}
+
+pub(crate) fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf_params: KdfParams,
+) -> Result<[u8; KEY_LENGTH]> {
+    let mut key = [0; KEY_LENGTH];
+    kdf_params
+        .to_argon2()?
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| PngError::MalformedPayload(format!("key derivation failed: {}", err)))?;
+    Ok(key)
+}
+
+/// Appends a random nonce to `header` and encrypts `plaintext` after it, authenticating the whole header
+fn seal(key: &[u8; KEY_LENGTH], mut header: Vec<u8>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut nonce = [0; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+    header.extend_from_slice(&nonce);
+
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| PngError::MalformedPayload("encryption failed".to_string()))?;
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Decrypts the ciphertext that follows the nonce at `data[header_length..]`
+fn open(key: &[u8; KEY_LENGTH], data: &[u8], header_length: usize) -> Result<Vec<u8>> {
+    let mut reader = ByteReader::new(&data[header_length..]);
+    let nonce = reader.read_array::<NONCE_LENGTH>()?;
+    let ciphertext = reader.rest();
+
+    let cipher = XChaCha20Poly1305::new(key.into());
+    cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &data[..header_length + NONCE_LENGTH],
+            },
+        )
+        .map_err(|_| PngError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters keep the tests fast, the format is the same
+    const TEST_KDF_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), 14 + PASSPHRASE_OVERHEAD);
+        assert!(!encrypted
+            .windows(b"secret message".len())
+            .any(|window| window == b"secret message"));
+        let decrypted = decrypt_with_passphrase(&encrypted, "correct horse").unwrap();
+        assert_eq!(decrypted, b"secret message");
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        let decrypted = decrypt_with_passphrase(&encrypted, "battery staple");
+
+        assert!(matches!(decrypted, Err(PngError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext() {
+        let mut encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        let decrypted = decrypt_with_passphrase(&encrypted, "correct horse");
+
+        assert!(matches!(decrypted, Err(PngError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_tampered_header() {
+        let mut encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        // Flip a bit of the salt
+        encrypted[20] ^= 1;
+        let decrypted = decrypt_with_passphrase(&encrypted, "correct horse");
+
+        assert!(matches!(decrypted, Err(PngError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_excessive_kdf_params() {
+        let mut encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        // memory KiB is stored right after magic, version and scheme
+        for memory_kib in [KdfParams::MAX_MEMORY_KIB + 1, u32::MAX] {
+            encrypted[6..10].copy_from_slice(&memory_kib.to_be_bytes());
+            let decrypted = decrypt_with_passphrase(&encrypted, "correct horse");
+
+            assert!(matches!(decrypted, Err(PngError::MalformedPayload(_))));
+        }
+        // Iterations follow the memory cost
+        encrypted[6..10].copy_from_slice(&TEST_KDF_PARAMS.memory_kib.to_be_bytes());
+        encrypted[10..14].copy_from_slice(&(KdfParams::MAX_ITERATIONS + 1).to_be_bytes());
+        let decrypted = decrypt_with_passphrase(&encrypted, "correct horse");
+
+        assert!(matches!(decrypted, Err(PngError::MalformedPayload(_))));
+    }
+
+    #[test]
+    fn test_recipients_round_trip() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let encrypted =
+            encrypt_to_recipients(b"secret message", &[alice.recipient(), bob.recipient()])
+                .unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), 14 + recipients_overhead(2));
+        assert_eq!(
+            decrypt_with_identity(&encrypted, &alice).unwrap(),
+            b"secret message"
+        );
+        assert_eq!(
+            decrypt_with_identity(&encrypted, &bob).unwrap(),
+            b"secret message"
+        );
+    }
+
+    #[test]
+    fn test_not_a_recipient() {
+        let alice = Identity::generate();
+        let eve = Identity::generate();
+        let encrypted = encrypt_to_recipients(b"secret message", &[alice.recipient()]).unwrap();
+        let decrypted = decrypt_with_identity(&encrypted, &eve);
+
+        assert!(matches!(decrypted, Err(PngError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_recipients_tampered_ciphertext() {
+        let alice = Identity::generate();
+        let mut encrypted = encrypt_to_recipients(b"secret message", &[alice.recipient()]).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        let decrypted = decrypt_with_identity(&encrypted, &alice);
+
+        assert!(matches!(decrypted, Err(PngError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_wrong_scheme() {
+        let alice = Identity::generate();
+        let encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        let decrypted = decrypt_with_identity(&encrypted, &alice);
+
+        assert!(matches!(decrypted, Err(PngError::MalformedPayload(_))));
+    }
+
+    #[test]
+    fn test_key_hex_round_trip() {
+        let identity = Identity::generate();
+        let restored = Identity::from_hex(&identity.to_hex()).unwrap();
+        assert_eq!(restored.recipient(), identity.recipient());
+
+        let recipient = Recipient::from_hex(&identity.recipient().to_hex()).unwrap();
+        assert_eq!(recipient, identity.recipient());
+
+        assert!(matches!(
+            Recipient::from_hex("not a key"),
+            Err(PngError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_low_order_recipient() {
+        let recipient = Recipient::from_hex(&"00".repeat(32)).unwrap();
+        let encrypted = encrypt_to_recipients(b"secret message", &[recipient]);
+
+        assert!(matches!(encrypted, Err(PngError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        let encrypted =
+            encrypt_with_passphrase(b"secret message", "correct horse", TEST_KDF_PARAMS).unwrap();
+        for length in 0..encrypted.len() {
+            assert!(decrypt_with_passphrase(&encrypted[..length], "correct horse").is_err());
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+/// Everything that can go wrong while parsing, modifying or writing a PNG.
+#[derive(Debug)]
+pub enum PngError {
+    /// The input ended before a complete structure could be read.
+    Truncated { needed: usize, available: usize },
+    /// The first 8 bytes are not the PNG signature.
+    InvalidSignature,
+    /// The CRC stored in a chunk does not match the one computed from its type and data.
+    /// `expected` is the value computed from the chunk, `actual` is the value found in the input.
+    CrcMismatch { expected: u32, actual: u32 },
+    /// A chunk type code is not made of 4 ASCII letters.
+    InvalidChunkType(String),
+    /// The IHDR chunk is missing fields or holds values the PNG specification does not allow.
+    InvalidHeader(String),
+    /// The IDAT data can't be decoded into the pixels the header describes.
+    InvalidImageData(String),
+    /// No chunk of the requested type exists.
+    ChunkNotFound(String),
+    /// Chunk data is longer than the 2^31-1 bytes a PNG chunk can hold.
+    ChunkTooLarge(usize),
+    /// A chunk position is past the end of the chunk list.
+    IndexOutOfBounds { index: usize, length: usize },
+    /// Chunk data was expected to be UTF-8 but is not.
+    InvalidUtf8(std::str::Utf8Error),
+    /// Embedded data does not follow the expected layout.
+    MalformedPayload(String),
+    /// The checksum stored in a payload container does not match the one computed from its content.
+    /// `expected` is the value computed from the content, `actual` is the value found in the input.
+    PayloadChecksumMismatch { expected: u32, actual: u32 },
+    /// A message split across several chunks is missing one of its fragments.
+    MissingFragment { sequence: u32, total: u32 },
+    /// Two chunks carry the same fragment of a split message.
+    DuplicateFragment { sequence: u32, total: u32 },
+    /// A tEXt, zTXt or iTXt chunk does not follow the PNG specification.
+    InvalidTextChunk(String),
+    /// Decompressed data would be larger than the given limit in bytes.
+    ExpansionLimitExceeded(usize),
+    /// A payload of `needed` bytes doesn't fit in the `capacity` bytes the image can hide.
+    CapacityExceeded { needed: usize, capacity: usize },
+    /// The image's pixel format can't hide data without visibly changing it.
+    UnsupportedCarrier(String),
+    /// Decryption failed because the key or passphrase is wrong or the data was modified.
+    DecryptionFailed,
+    /// An Ed25519 signature over embedded data does not verify.
+    SignatureVerificationFailed,
+    /// Embedded data is signed by a key that is not in the trusted list.
+    UntrustedSigner(String),
+    /// A key could not be parsed or cannot be used.
+    InvalidKey(String),
+    /// Command line options or function arguments that can't be used together or are out of range.
+    InvalidArgument(String),
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, PngError>;
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::Truncated { needed, available } => write!(
+                f,
+                "input is truncated: needed {} bytes but only {} are available",
+                needed, available
+            ),
+            PngError::InvalidSignature => write!(f, "input does not start with the PNG signature"),
+            PngError::CrcMismatch { expected, actual } => write!(
+                f,
+                "CRC mismatch: computed {:#010x} but chunk stores {:#010x}",
+                expected, actual
+            ),
+            PngError::InvalidChunkType(chunk_type) => {
+                write!(f, "invalid chunk type {:?}", chunk_type)
+            }
+            PngError::InvalidHeader(reason) => write!(f, "invalid image header: {}", reason),
+            PngError::InvalidImageData(reason) => write!(f, "invalid image data: {}", reason),
+            PngError::ChunkNotFound(chunk_type) => write!(f, "no chunk of type {}", chunk_type),
+            PngError::ChunkTooLarge(length) => write!(
+                f,
+                "chunk data of {} bytes exceeds the PNG limit of {} bytes",
+                length,
+                crate::Chunk::MAX_LENGTH
+            ),
+            PngError::IndexOutOfBounds { index, length } => write!(
+                f,
+                "chunk index {} is out of bounds for a PNG with {} chunks",
+                index, length
+            ),
+            PngError::InvalidUtf8(err) => write!(f, "chunk data is not valid UTF-8: {}", err),
+            PngError::MalformedPayload(reason) => write!(f, "malformed payload: {}", reason),
+            PngError::PayloadChecksumMismatch { expected, actual } => write!(
+                f,
+                "payload checksum mismatch: computed {:#010x} but the container stores {:#010x}",
+                expected, actual
+            ),
+            PngError::MissingFragment { sequence, total } => write!(
+                f,
+                "fragment {} of {} is missing, the message is incomplete",
+                sequence + 1,
+                total
+            ),
+            PngError::DuplicateFragment { sequence, total } => write!(
+                f,
+                "fragment {} of {} appears more than once",
+                sequence + 1,
+                total
+            ),
+            PngError::InvalidTextChunk(reason) => write!(f, "invalid text chunk: {}", reason),
+            PngError::ExpansionLimitExceeded(limit) => write!(
+                f,
+                "decompressed data would exceed the limit of {} bytes",
+                limit
+            ),
+            PngError::CapacityExceeded { needed, capacity } => write!(
+                f,
+                "payload of {} bytes doesn't fit, the image can hide {} bytes",
+                needed, capacity
+            ),
+            PngError::UnsupportedCarrier(reason) => write!(f, "unsupported carrier: {}", reason),
+            PngError::DecryptionFailed => write!(
+                f,
+                "decryption failed: wrong passphrase or key, or the data was tampered with"
+            ),
+            PngError::SignatureVerificationFailed => write!(
+                f,
+                "signature verification failed: the data or image was modified"
+            ),
+            PngError::UntrustedSigner(key) => write!(f, "signed by untrusted key {}", key),
+            PngError::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            PngError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            PngError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::InvalidUtf8(err) => Some(err),
+            PngError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::str::Utf8Error> for PngError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        PngError::InvalidUtf8(err)
+    }
+}
+
+impl From<std::io::Error> for PngError {
+    fn from(err: std::io::Error) -> Self {
+        PngError::Io(err)
+    }
+}
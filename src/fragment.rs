@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand_core::{OsRng, RngCore};
+
+use crate::{
+    byte_reader::ByteReader,
+    chunk::Chunk,
+    error::{PngError, Result},
+};
+
+// Fragment layout:
+// magic (4) | version (1) | message id (4) | sequence number (4) | fragment count (4) | data
+// Fragments of one message share a random message id, so several split messages can live in the
+// same chunk type. Sequence numbers start at 0.
+const MAGIC: [u8; 4] = *b"PMFR";
+const VERSION: u8 = 1;
+/// Bytes taken by the fragment header in front of each piece of the message
+pub const HEADER_LENGTH: usize = 17;
+
+/// Returns true if `data` starts with the header written by `split`
+pub fn is_fragment(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Splits `message` into pieces of chunk data that are at most `max_chunk_length` bytes long.
+///
+/// A message that already fits is returned unchanged as a single piece, so it stays readable
+/// without reassembly. `max_chunk_length` is capped at `Chunk::MAX_LENGTH`.
+pub fn split(message: &[u8], max_chunk_length: usize) -> Result<Vec<Vec<u8>>> {
+    let max_chunk_length = max_chunk_length.min(Chunk::MAX_LENGTH);
+    if message.len() <= max_chunk_length {
+        return Ok(vec![message.to_vec()]);
+    }
+    if max_chunk_length <= HEADER_LENGTH {
+        return Err(PngError::MalformedPayload(format!(
+            "chunks of {} bytes can't hold a fragment header",
+            max_chunk_length
+        )));
+    }
+
+    let pieces = message.chunks(max_chunk_length - HEADER_LENGTH);
+    let total = u32::try_from(pieces.len())
+        .map_err(|_| PngError::MalformedPayload("message needs too many fragments".to_string()))?;
+    let message_id = OsRng.next_u32();
+    Ok(pieces
+        .zip(0..)
+        .map(|(piece, sequence)| {
+            let mut fragment = Vec::with_capacity(HEADER_LENGTH + piece.len());
+            fragment.extend_from_slice(&MAGIC);
+            fragment.push(VERSION);
+            fragment.extend_from_slice(&message_id.to_be_bytes());
+            fragment.extend_from_slice(&u32::to_be_bytes(sequence));
+            fragment.extend_from_slice(&total.to_be_bytes());
+            fragment.extend_from_slice(piece);
+            fragment
+        })
+        .collect())
+}
+
+/// Number of chunks `split` cuts a message of `message_length` bytes into, `None` when
+/// `max_chunk_length` can't hold a fragment header and the message needs splitting
+pub fn piece_count(message_length: usize, max_chunk_length: usize) -> Option<usize> {
+    let max_chunk_length = max_chunk_length.min(Chunk::MAX_LENGTH);
+    if message_length <= max_chunk_length {
+        Some(1)
+    } else if max_chunk_length <= HEADER_LENGTH {
+        None
+    } else {
+        Some(message_length.div_ceil(max_chunk_length - HEADER_LENGTH))
+    }
+}
+
+/// One piece of a message written by `split`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragment<'a> {
+    message_id: u32,
+    sequence: u32,
+    total: u32,
+    data: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Fragment<'a>> {
+        let mut reader = ByteReader::new(data);
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(PngError::MalformedPayload(
+                "data is not a fragment".to_string(),
+            ));
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(PngError::MalformedPayload(format!(
+                "unsupported fragment version {}",
+                version
+            )));
+        }
+        let message_id = reader.read_u32()?;
+        let sequence = reader.read_u32()?;
+        let total = reader.read_u32()?;
+        if sequence >= total {
+            return Err(PngError::MalformedPayload(format!(
+                "fragment number {} is out of range for a message of {} fragments",
+                sequence, total
+            )));
+        }
+
+        Ok(Fragment {
+            message_id,
+            sequence,
+            total,
+            data: reader.rest(),
+        })
+    }
+
+    pub fn message_id(&self) -> u32 {
+        self.message_id
+    }
+
+    /// Position of this fragment in the message, starting at 0
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Number of fragments the message was split into
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+enum Message<'a> {
+    Whole(&'a [u8]),
+    Split {
+        total: u32,
+        fragments: BTreeMap<u32, &'a [u8]>,
+    },
+}
+
+/// Rebuilds the messages stored in a sequence of chunk data, in the order of each message's
+/// first chunk. Fragments may appear in any order; data that is not a fragment is its own message.
+///
+/// Returns `PngError::MissingFragment` or `PngError::DuplicateFragment` when a split message is
+/// incomplete or has a fragment twice.
+pub fn reassemble<'a>(chunk_data: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<Vec<u8>>> {
+    let mut messages = Vec::new();
+    let mut positions_by_id = HashMap::new();
+    for data in chunk_data {
+        if !is_fragment(data) {
+            messages.push(Message::Whole(data));
+            continue;
+        }
+
+        let fragment = Fragment::parse(data)?;
+        let position = *positions_by_id
+            .entry(fragment.message_id)
+            .or_insert_with(|| {
+                messages.push(Message::Split {
+                    total: fragment.total,
+                    // A map rather than a preallocated list, so a forged count can't force a huge allocation
+                    fragments: BTreeMap::new(),
+                });
+                messages.len() - 1
+            });
+        let Message::Split { total, fragments } = &mut messages[position] else {
+            unreachable!("message ids only point at split messages");
+        };
+        if *total != fragment.total {
+            return Err(PngError::MalformedPayload(format!(
+                "fragments of message {:08x} disagree on the fragment count",
+                fragment.message_id
+            )));
+        }
+        if fragments.insert(fragment.sequence, fragment.data).is_some() {
+            return Err(PngError::DuplicateFragment {
+                sequence: fragment.sequence,
+                total: fragment.total,
+            });
+        }
+    }
+
+    messages
+        .into_iter()
+        .map(|message| match message {
+            Message::Whole(data) => Ok(data.to_vec()),
+            Message::Split { total, fragments } => {
+                let mut data = Vec::new();
+                for sequence in 0..total {
+                    match fragments.get(&sequence) {
+                        Some(piece) => data.extend_from_slice(piece),
+                        None => return Err(PngError::MissingFragment { sequence, total }),
+                    }
+                }
+                Ok(data)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_slices(pieces: &[Vec<u8>]) -> Vec<&[u8]> {
+        pieces.iter().map(Vec::as_slice).collect()
+    }
+
+    #[test]
+    fn test_small_message_is_not_split() {
+        let pieces = split(b"short message", 100).unwrap();
+        assert_eq!(pieces, vec![b"short message".to_vec()]);
+        assert_eq!(piece_count(b"short message".len(), 100), Some(1));
+        assert_eq!(
+            reassemble(as_slices(&pieces)).unwrap(),
+            vec![b"short message".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let message: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let pieces = split(&message, 100).unwrap();
+
+        assert_eq!(pieces.len(), 13);
+        assert_eq!(piece_count(message.len(), 100), Some(13));
+        assert!(pieces.iter().all(|piece| piece.len() <= 100));
+        assert!(pieces.iter().all(|piece| is_fragment(piece)));
+        assert_eq!(reassemble(as_slices(&pieces)).unwrap(), vec![message]);
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order_and_interleaved() {
+        let first: Vec<u8> = vec![1; 300];
+        let second: Vec<u8> = vec![2; 300];
+        let first_pieces = split(&first, 100).unwrap();
+        let second_pieces = split(&second, 100).unwrap();
+
+        let mut chunk_data = as_slices(&first_pieces);
+        chunk_data.reverse();
+        chunk_data.insert(1, b"plain message");
+        chunk_data.extend(as_slices(&second_pieces));
+
+        assert_eq!(
+            reassemble(chunk_data).unwrap(),
+            vec![first, b"plain message".to_vec(), second]
+        );
+    }
+
+    #[test]
+    fn test_missing_fragment() {
+        let pieces = split(&[7; 300], 100).unwrap();
+        let mut chunk_data = as_slices(&pieces);
+        chunk_data.remove(2);
+
+        assert!(matches!(
+            reassemble(chunk_data),
+            Err(PngError::MissingFragment {
+                sequence: 2,
+                total: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_fragment() {
+        let pieces = split(&[7; 300], 100).unwrap();
+        let mut chunk_data = as_slices(&pieces);
+        chunk_data.push(chunk_data[1]);
+
+        assert!(matches!(
+            reassemble(chunk_data),
+            Err(PngError::DuplicateFragment {
+                sequence: 1,
+                total: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_max_length_too_small_for_header() {
+        assert!(split(&[7; 300], HEADER_LENGTH).is_err());
+        assert_eq!(piece_count(300, HEADER_LENGTH), None);
+    }
+
+    #[test]
+    fn test_invalid_fragment_header() {
+        let pieces = split(&[7; 300], 100).unwrap();
+        for length in 0..HEADER_LENGTH {
+            assert!(Fragment::parse(&pieces[0][..length]).is_err());
+        }
+
+        let mut out_of_range = pieces[0].clone();
+        out_of_range[9..13].copy_from_slice(&4u32.to_be_bytes());
+        assert!(matches!(
+            Fragment::parse(&out_of_range),
+            Err(PngError::MalformedPayload(_))
+        ));
+    }
+}
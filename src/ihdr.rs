@@ -0,0 +1,316 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::{
+    byte_reader::ByteReader,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{PngError, Result},
+};
+
+// IHDR layout:
+// width (4) | height (4) | bit depth (1) | color type (1) | compression method (1) |
+// filter method (1) | interlace method (1)
+const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+/// How pixels are made up of samples, from the `IHDR` color type field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn from_byte(byte: u8) -> Result<ColorType> {
+        match byte {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(PngError::InvalidHeader(format!(
+                "unknown color type {}",
+                byte
+            ))),
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    /// Number of samples (channels) in each pixel; indexed pixels are a single palette index
+    pub fn samples_per_pixel(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Bit depths the PNG specification allows for this color type
+    pub fn allowed_bit_depths(self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Order in which the image's pixels are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interlace {
+    None,
+    Adam7,
+}
+
+impl Display for Interlace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interlace::None => write!(f, "none"),
+            Interlace::Adam7 => write!(f, "Adam7"),
+        }
+    }
+}
+
+/// The image header, which is always the first chunk of a PNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    /// Always 0 (deflate) in valid files
+    pub compression_method: u8,
+    /// Always 0 (adaptive filtering) in valid files
+    pub filter_method: u8,
+    pub interlace: Interlace,
+}
+
+impl Ihdr {
+    /// Length of the `IHDR` chunk data
+    pub const LENGTH: usize = 13;
+
+    /// Builds a validated header with the only compression and filter methods PNG defines
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        interlace: Interlace,
+    ) -> Result<Ihdr> {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace,
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+
+    /// Checks the dimensions, the bit depth/color type combination and the method fields
+    pub fn validate(&self) -> Result<()> {
+        for (name, dimension) in [("width", self.width), ("height", self.height)] {
+            if dimension == 0 || dimension > MAX_DIMENSION {
+                return Err(PngError::InvalidHeader(format!(
+                    "{} must be between 1 and {}, not {}",
+                    name, MAX_DIMENSION, dimension
+                )));
+            }
+        }
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(PngError::InvalidHeader(format!(
+                "bit depth {} is not allowed for {} images",
+                self.bit_depth, self.color_type
+            )));
+        }
+        if self.compression_method != 0 {
+            return Err(PngError::InvalidHeader(format!(
+                "unknown compression method {}",
+                self.compression_method
+            )));
+        }
+        if self.filter_method != 0 {
+            return Err(PngError::InvalidHeader(format!(
+                "unknown filter method {}",
+                self.filter_method
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.samples_per_pixel() * self.bit_depth as usize
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(Ihdr::LENGTH);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.push(self.bit_depth);
+        data.push(self.color_type.to_byte());
+        data.push(self.compression_method);
+        data.push(self.filter_method);
+        data.push(match self.interlace {
+            Interlace::None => 0,
+            Interlace::Adam7 => 1,
+        });
+        Chunk::try_new(
+            ChunkType::from_str("IHDR").expect("IHDR is a valid chunk type"),
+            data,
+        )
+        .expect("IHDR data is 13 bytes long")
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Ihdr> {
+        if chunk.chunk_type().bytes() != *b"IHDR" {
+            return Err(PngError::InvalidHeader(format!(
+                "expected an IHDR chunk, found {}",
+                chunk.chunk_type()
+            )));
+        }
+        if chunk.data().len() != Ihdr::LENGTH {
+            return Err(PngError::InvalidHeader(format!(
+                "IHDR must be {} bytes long, not {}",
+                Ihdr::LENGTH,
+                chunk.data().len()
+            )));
+        }
+
+        let mut reader = ByteReader::new(chunk.data());
+        let width = reader.read_u32()?;
+        let height = reader.read_u32()?;
+        let bit_depth = reader.read_u8()?;
+        let color_type = ColorType::from_byte(reader.read_u8()?)?;
+        let compression_method = reader.read_u8()?;
+        let filter_method = reader.read_u8()?;
+        let interlace = match reader.read_u8()? {
+            0 => Interlace::None,
+            1 => Interlace::Adam7,
+            method => {
+                return Err(PngError::InvalidHeader(format!(
+                    "unknown interlace method {}",
+                    method
+                )))
+            }
+        };
+
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method,
+            filter_method,
+            interlace,
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(data: &[u8]) -> Chunk {
+        Chunk::try_new(ChunkType::from_str("IHDR").unwrap(), data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_ihdr() {
+        let chunk = ihdr_chunk(&[0, 0, 1, 0, 0, 0, 0, 200, 8, 6, 0, 0, 1]);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+
+        assert_eq!(ihdr.width, 256);
+        assert_eq!(ihdr.height, 200);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace, Interlace::Adam7);
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ihdr = Ihdr::new(64, 48, 4, ColorType::Indexed, Interlace::None).unwrap();
+        assert_eq!(Ihdr::try_from(&ihdr.to_chunk()).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_bit_depth_color_type_combinations() {
+        let color_types = [
+            ColorType::Grayscale,
+            ColorType::Rgb,
+            ColorType::Indexed,
+            ColorType::GrayscaleAlpha,
+            ColorType::Rgba,
+        ];
+        let mut allowed = 0;
+        for color_type in color_types {
+            for bit_depth in [1, 2, 3, 4, 8, 16, 32] {
+                if Ihdr::new(1, 1, bit_depth, color_type, Interlace::None).is_ok() {
+                    allowed += 1;
+                }
+            }
+        }
+        // The PNG specification lists 15 valid combinations
+        assert_eq!(allowed, 15);
+        assert!(Ihdr::new(1, 1, 16, ColorType::Indexed, Interlace::None).is_err());
+        assert!(Ihdr::new(1, 1, 4, ColorType::Rgb, Interlace::None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let valid = [0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+        for (index, value) in [(3, 0), (0, 0x80), (9, 5), (10, 1), (11, 1), (12, 2)] {
+            let mut data = valid;
+            data[index] = value;
+            assert!(
+                matches!(
+                    Ihdr::try_from(&ihdr_chunk(&data)),
+                    Err(PngError::InvalidHeader(_))
+                ),
+                "byte {} = {} should be rejected",
+                index,
+                value
+            );
+        }
+        assert!(Ihdr::try_from(&ihdr_chunk(&valid[..12])).is_err());
+    }
+}
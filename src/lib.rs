@@ -0,0 +1,35 @@
+//! Library for reading, modifying and writing PNG files chunk by chunk.
+//!
+//! The `pngme_bin` command line tool is built on top of this crate, but the
+//! same types can be used directly to parse and encode PNG data.
+
+pub mod analysis;
+mod byte_reader;
+pub mod capacity;
+pub mod chunk;
+pub mod chunk_type;
+pub mod compression;
+pub mod container;
+pub mod crypto;
+pub mod error;
+pub mod fragment;
+pub mod ihdr;
+pub mod lsb;
+pub mod payload;
+pub mod pixels;
+pub mod png;
+pub mod reader;
+pub mod signing;
+#[cfg(test)]
+mod test_util;
+pub mod text_chunk;
+pub mod trailer;
+pub mod writer;
+
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use error::{PngError, Result};
+pub use ihdr::Ihdr;
+pub use png::Png;
+pub use reader::PngReader;
+pub use writer::PngWriter;
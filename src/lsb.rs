@@ -0,0 +1,603 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::{
+    crypto::{self, KdfParams},
+    error::{PngError, Result},
+    ihdr::{ColorType, Ihdr},
+    pixels::PixelBuffer,
+};
+
+// Embedded data layout, most significant bit first:
+// payload length (4) | payload
+// The bits are cut into groups of `matrix_bits`, the last one padded with zeros, and each group
+// is stored in the next 2^matrix_bits - 1 carrier samples with a Hamming code: the group is the
+// syndrome of the samples' lowest bits, which takes at most one flip to set. With one bit per
+// group, each sample's lowest bit is simply one payload bit. With `bits_per_sample` of 2, each
+// sample counts as two carriers, its lowest bit followed by the one above it.
+const LENGTH_PREFIX: usize = 4;
+// The order must be reproducible from the key alone, so its derivation uses a fixed salt
+const ORDER_SALT: &[u8] = b"pngme lsb embedding order";
+
+/// How payload bits are spread over the image. Extraction needs the options used for embedding.
+#[derive(Debug, Clone)]
+pub struct LsbOptions {
+    /// Passphrase seeding the pseudo-random order in which samples carry bits. Without it,
+    /// bits go into samples in pixel order, which anyone can read back.
+    pub key: Option<String>,
+    /// Argon2id cost of turning `key` into the seed
+    pub kdf_params: KdfParams,
+    /// Payload bits per block of 2^matrix_bits - 1 samples, from 1 to `MAX_MATRIX_BITS`. Each block
+    /// changes at most one sample, so higher values change fewer samples but hold less data:
+    /// 1 stores a bit in every sample, 3 stores 3 bits in 7 samples.
+    pub matrix_bits: u8,
+    /// Low bits of each sample that carry data, from 1 to `MAX_BITS_PER_SAMPLE`. Two bits double
+    /// the capacity but change samples by up to 3 instead of 1.
+    pub bits_per_sample: u8,
+}
+
+impl LsbOptions {
+    pub const MAX_MATRIX_BITS: u8 = 8;
+    pub const MAX_BITS_PER_SAMPLE: u8 = 2;
+
+    fn checked_matrix_bits(&self) -> Result<usize> {
+        if !(1..=LsbOptions::MAX_MATRIX_BITS).contains(&self.matrix_bits) {
+            return Err(PngError::InvalidArgument(format!(
+                "matrix embedding carries 1 to {} bits per block, not {}",
+                LsbOptions::MAX_MATRIX_BITS,
+                self.matrix_bits
+            )));
+        }
+        Ok(self.matrix_bits as usize)
+    }
+
+    fn checked_bits_per_sample(&self) -> Result<usize> {
+        if !(1..=LsbOptions::MAX_BITS_PER_SAMPLE).contains(&self.bits_per_sample) {
+            return Err(PngError::InvalidArgument(format!(
+                "LSB embedding uses 1 to {} bits per sample, not {}",
+                LsbOptions::MAX_BITS_PER_SAMPLE,
+                self.bits_per_sample
+            )));
+        }
+        Ok(self.bits_per_sample as usize)
+    }
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        LsbOptions {
+            key: None,
+            kdf_params: KdfParams::default(),
+            matrix_bits: 1,
+            bits_per_sample: 1,
+        }
+    }
+}
+
+/// The sample bits that carry data: the lowest `bits_per_sample` bits of every color sample of 8
+/// and 16-bit images, in pixel order. Alpha is left alone, and indexed or low bit depth images are
+/// refused because flipping their lowest bit visibly changes the color.
+struct Carriers {
+    color_channels: usize,
+    samples_per_pixel: usize,
+    bytes_per_sample: usize,
+    bits_per_sample: usize,
+    count: usize,
+}
+
+impl Carriers {
+    fn new(ihdr: &Ihdr, options: &LsbOptions) -> Result<Carriers> {
+        let bits_per_sample = options.checked_bits_per_sample()?;
+        if ihdr.color_type == ColorType::Indexed {
+            return Err(PngError::UnsupportedCarrier(
+                "indexed images can't carry LSB data, their samples are palette indices"
+                    .to_string(),
+            ));
+        }
+        if ihdr.bit_depth < 8 {
+            return Err(PngError::UnsupportedCarrier(format!(
+                "{}-bit images can't carry LSB data, only 8 and 16-bit ones",
+                ihdr.bit_depth
+            )));
+        }
+        let samples_per_pixel = ihdr.color_type.samples_per_pixel();
+        let color_channels = match ihdr.color_type {
+            ColorType::GrayscaleAlpha | ColorType::Rgba => samples_per_pixel - 1,
+            _ => samples_per_pixel,
+        };
+        Ok(Carriers {
+            color_channels,
+            samples_per_pixel,
+            bytes_per_sample: ihdr.bit_depth as usize / 8,
+            bits_per_sample,
+            count: ihdr.width as usize * ihdr.height as usize * color_channels * bits_per_sample,
+        })
+    }
+
+    /// Offset of the byte holding the `index`th carrier bit and the mask selecting it. 16-bit
+    /// samples are big-endian, so their low bits are in the second byte.
+    fn locate(&self, index: usize) -> (usize, u8) {
+        let sample = index / self.bits_per_sample;
+        let pixel = sample / self.color_channels;
+        let channel = sample % self.color_channels;
+        let offset = (pixel * self.samples_per_pixel + channel + 1) * self.bytes_per_sample - 1;
+        (offset, 1 << (index % self.bits_per_sample))
+    }
+
+    fn capacity(&self, matrix_bits: usize) -> usize {
+        let blocks = self.count / block_length(matrix_bits);
+        (blocks * matrix_bits / 8).saturating_sub(LENGTH_PREFIX)
+    }
+
+    /// Carrier indices in the order they receive bits
+    fn order(&self, options: &LsbOptions) -> Result<Order> {
+        let shuffle = match &options.key {
+            Some(key) => {
+                let seed = crypto::derive_key(key, ORDER_SALT, options.kdf_params)?;
+                Some((ChaCha20Rng::from_seed(seed), HashMap::new()))
+            }
+            None => None,
+        };
+        Ok(Order {
+            count: self.count,
+            next: 0,
+            shuffle,
+        })
+    }
+}
+
+/// Yields carrier indices either in sequence or as a keyed random permutation. The permutation is
+/// a Fisher-Yates shuffle computed lazily, remembering only the swapped entries, so its cost
+/// follows the payload size rather than the image size.
+struct Order {
+    count: usize,
+    next: usize,
+    shuffle: Option<(ChaCha20Rng, HashMap<usize, usize>)>,
+}
+
+impl Iterator for Order {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next >= self.count {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+        let Some((rng, swapped)) = &mut self.shuffle else {
+            return Some(index);
+        };
+        let other = index + uniform(rng, self.count - index);
+        let at_index = swapped.remove(&index).unwrap_or(index);
+        let at_other = if other == index {
+            at_index
+        } else {
+            swapped.insert(other, at_index).unwrap_or(other)
+        };
+        Some(at_other)
+    }
+}
+
+/// Uniformly distributed number below `bound`, rejecting the values that would bias the modulo
+fn uniform(rng: &mut ChaCha20Rng, bound: usize) -> usize {
+    let bound = bound as u64;
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let value = rng.next_u64();
+        if value < zone {
+            return (value % bound) as usize;
+        }
+    }
+}
+
+/// Number of payload bytes an image with this header can carry with `options.matrix_bits` and
+/// `options.bits_per_sample`
+pub fn capacity(ihdr: &Ihdr, options: &LsbOptions) -> Result<usize> {
+    Ok(Carriers::new(ihdr, options)?.capacity(options.checked_matrix_bits()?))
+}
+
+/// Writes `payload` into the least-significant bits of the image's color samples and returns
+/// how many samples changed. Fails with `PngError::CapacityExceeded`, leaving the pixels
+/// untouched, when it doesn't fit.
+pub fn embed(pixels: &mut PixelBuffer, payload: &[u8], options: &LsbOptions) -> Result<usize> {
+    let matrix_bits = options.checked_matrix_bits()?;
+    let carriers = Carriers::new(pixels.ihdr(), options)?;
+    let capacity = carriers.capacity(matrix_bits);
+    if payload.len() > capacity {
+        return Err(PngError::CapacityExceeded {
+            needed: payload.len(),
+            capacity,
+        });
+    }
+    let length = u32::try_from(payload.len()).map_err(|_| PngError::CapacityExceeded {
+        needed: payload.len(),
+        capacity: u32::MAX as usize,
+    })?;
+
+    let mut order = carriers.order(options)?;
+    let data = pixels.data_mut();
+    let mut bits = length
+        .to_be_bytes()
+        .into_iter()
+        .chain(payload.iter().copied())
+        .flat_map(|byte| (0..8).rev().map(move |bit_index| (byte >> bit_index) & 1))
+        .peekable();
+    let mut block = Vec::with_capacity(block_length(matrix_bits));
+    let mut modified = HashSet::new();
+    while bits.peek().is_some() {
+        // The last block is padded with zero bits
+        let message = (0..matrix_bits).fold(0, |message, _| {
+            (message << 1) | bits.next().unwrap_or(0) as usize
+        });
+        block.clear();
+        block.extend(
+            order
+                .by_ref()
+                .take(block_length(matrix_bits))
+                .map(|index| carriers.locate(index)),
+        );
+        // Flipping the sample at position `syndrome ^ message` (1-based) turns the syndrome into `message`
+        let flip = syndrome(data, &block) ^ message;
+        if flip != 0 {
+            let (offset, mask) = block[flip - 1];
+            data[offset] ^= mask;
+            modified.insert(offset);
+        }
+    }
+    Ok(modified.len())
+}
+
+/// Reads back a payload written by `embed` with the same options. A wrong key reads noise, which
+/// is reported as `PngError::MalformedPayload` when the length it yields can't be right.
+pub fn extract(pixels: &PixelBuffer, options: &LsbOptions) -> Result<Vec<u8>> {
+    let matrix_bits = options.checked_matrix_bits()?;
+    let carriers = Carriers::new(pixels.ihdr(), options)?;
+    if carriers.count / block_length(matrix_bits) * matrix_bits < LENGTH_PREFIX * 8 {
+        return Err(PngError::MalformedPayload(
+            "the image is too small to carry LSB data".to_string(),
+        ));
+    }
+    let mut order = carriers.order(options)?;
+    let data = pixels.data();
+    let mut block = Vec::with_capacity(block_length(matrix_bits));
+    let mut pending = VecDeque::with_capacity(matrix_bits);
+    let mut read_byte = || {
+        (0..8).fold(0, |byte, _| {
+            if pending.is_empty() {
+                block.clear();
+                block.extend(
+                    order
+                        .by_ref()
+                        .take(block_length(matrix_bits))
+                        .map(|index| carriers.locate(index)),
+                );
+                let message = syndrome(data, &block);
+                pending.extend(
+                    (0..matrix_bits)
+                        .rev()
+                        .map(|bit_index| (message >> bit_index) as u8 & 1),
+                );
+            }
+            (byte << 1)
+                | pending
+                    .pop_front()
+                    .expect("a block yields at least one bit")
+        })
+    };
+
+    let length = u32::from_be_bytes(std::array::from_fn(|_| read_byte())) as usize;
+    let capacity = carriers.capacity(matrix_bits);
+    if length > capacity {
+        return Err(PngError::MalformedPayload(format!(
+            "LSB length prefix of {} bytes exceeds the image's capacity of {}, \
+             the image carries no LSB data or the key or matrix bits are wrong",
+            length, capacity
+        )));
+    }
+    Ok((0..length).map(|_| read_byte()).collect())
+}
+
+/// Samples in a Hamming code block carrying `matrix_bits` bits
+fn block_length(matrix_bits: usize) -> usize {
+    (1 << matrix_bits) - 1
+}
+
+/// XOR of the 1-based positions of the block's carrier bits that are set, which is the Hamming
+/// code syndrome of those bits
+fn syndrome(data: &[u8], block: &[(usize, u8)]) -> usize {
+    block
+        .iter()
+        .enumerate()
+        .filter(|(_, &(offset, mask))| data[offset] & mask != 0)
+        .fold(0, |syndrome, (position, _)| syndrome ^ (position + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ihdr::Interlace, test_util};
+
+    // Cheap parameters keep the tests fast, the order only depends on the derived seed
+    fn keyed(key: &str) -> LsbOptions {
+        LsbOptions {
+            key: Some(key.to_string()),
+            kdf_params: KdfParams {
+                memory_kib: 64,
+                iterations: 1,
+                parallelism: 1,
+            },
+            ..LsbOptions::default()
+        }
+    }
+
+    fn noise_pixels(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> PixelBuffer {
+        let ihdr = Ihdr::new(width, height, bit_depth, color_type, Interlace::None).unwrap();
+        let length = (width as usize * ihdr.bits_per_pixel()).div_ceil(8) * height as usize;
+        PixelBuffer::new(ihdr, test_util::noise(0x2545_f491, length)).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_all_carrier_formats() {
+        let formats = [
+            (8, ColorType::Grayscale),
+            (16, ColorType::Grayscale),
+            (8, ColorType::Rgb),
+            (16, ColorType::Rgb),
+            (8, ColorType::GrayscaleAlpha),
+            (16, ColorType::GrayscaleAlpha),
+            (8, ColorType::Rgba),
+            (16, ColorType::Rgba),
+        ];
+        for (bit_depth, color_type) in formats {
+            let mut pixels = noise_pixels(20, 10, bit_depth, color_type);
+            let original = pixels.clone();
+            let payload: Vec<u8> =
+                (0..capacity(pixels.ihdr(), &LsbOptions::default()).unwrap() as u8).collect();
+            embed(&mut pixels, &payload, &LsbOptions::default()).unwrap();
+            assert_eq!(
+                extract(&pixels, &LsbOptions::default()).unwrap(),
+                payload,
+                "{}-bit {}",
+                bit_depth,
+                color_type
+            );
+
+            // Only the lowest bit of color samples changes
+            let has_alpha = matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba);
+            for y in 0..10 {
+                for x in 0..20 {
+                    for channel in 0..color_type.samples_per_pixel() {
+                        let before = original.sample(x, y, channel);
+                        let after = pixels.sample(x, y, channel);
+                        assert_eq!(before | 1, after | 1);
+                        if has_alpha && channel == color_type.samples_per_pixel() - 1 {
+                            assert_eq!(before, after);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let pixels = noise_pixels(20, 10, 8, ColorType::Rgba);
+        // 600 color samples hold 75 bytes, 4 of which store the length
+        assert_eq!(capacity(pixels.ihdr(), &LsbOptions::default()).unwrap(), 71);
+        let pixels = noise_pixels(20, 10, 16, ColorType::Grayscale);
+        assert_eq!(capacity(pixels.ihdr(), &LsbOptions::default()).unwrap(), 21);
+    }
+
+    #[test]
+    fn test_payload_too_large() {
+        let mut pixels = noise_pixels(20, 10, 8, ColorType::Rgb);
+        let original = pixels.clone();
+        assert!(matches!(
+            embed(&mut pixels, &[0; 72], &keyed("key")),
+            Err(PngError::CapacityExceeded {
+                needed: 72,
+                capacity: 71
+            })
+        ));
+        assert_eq!(pixels.data(), original.data());
+    }
+
+    #[test]
+    fn test_unsupported_carriers() {
+        for (bit_depth, color_type) in [(8, ColorType::Indexed), (4, ColorType::Grayscale)] {
+            let mut pixels = noise_pixels(8, 8, bit_depth, color_type);
+            assert!(matches!(
+                embed(&mut pixels, b"secret", &LsbOptions::default()),
+                Err(PngError::UnsupportedCarrier(_))
+            ));
+            assert!(extract(&pixels, &LsbOptions::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_extract_without_payload() {
+        let ihdr = Ihdr::new(20, 10, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let pixels = PixelBuffer::new(ihdr, vec![255; 600]).unwrap();
+        assert!(matches!(
+            extract(&pixels, &LsbOptions::default()),
+            Err(PngError::MalformedPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_keyed_order_round_trip() {
+        for (bit_depth, color_type) in [(8, ColorType::Rgb), (16, ColorType::Rgba)] {
+            let mut pixels = noise_pixels(20, 10, bit_depth, color_type);
+            let capacity = capacity(pixels.ihdr(), &LsbOptions::default()).unwrap();
+            for length in [0, 5, capacity] {
+                let payload: Vec<u8> = (0..length as u8).collect();
+                embed(&mut pixels, &payload, &keyed("correct horse")).unwrap();
+                assert_eq!(extract(&pixels, &keyed("correct horse")).unwrap(), payload);
+            }
+        }
+    }
+
+    #[test]
+    fn test_keyed_order_is_a_permutation() {
+        let pixels = noise_pixels(20, 10, 8, ColorType::Rgb);
+        let carriers = Carriers::new(pixels.ihdr(), &LsbOptions::default()).unwrap();
+        let mut order: Vec<usize> = carriers.order(&keyed("key")).unwrap().collect();
+        assert_ne!(order, (0..600).collect::<Vec<usize>>());
+        order.sort_unstable();
+        assert_eq!(order, (0..600).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_keyed_payload_is_scattered() {
+        let mut pixels = noise_pixels(20, 10, 8, ColorType::Rgb);
+        let original = pixels.clone();
+        // Set every bit, so every carrier the payload touches has its lowest bit set
+        embed(&mut pixels, &[0xff; 8], &keyed("key")).unwrap();
+        let set: Vec<usize> = (0..600)
+            .filter(|&index| pixels.data()[index] != original.data()[index])
+            .collect();
+        assert!(set.iter().any(|&index| index >= 96));
+    }
+
+    #[test]
+    fn test_matrix_embedding_round_trip() {
+        for matrix_bits in 1..=LsbOptions::MAX_MATRIX_BITS {
+            for options in [LsbOptions::default(), keyed("key")] {
+                let options = LsbOptions {
+                    matrix_bits,
+                    ..options
+                };
+                let mut pixels = noise_pixels(64, 64, 8, ColorType::Rgb);
+                let original = pixels.clone();
+                let capacity = capacity(pixels.ihdr(), &options).unwrap();
+                let payload: Vec<u8> = (0..capacity).map(|byte| (byte * 7) as u8).collect();
+
+                let modified = embed(&mut pixels, &payload, &options).unwrap();
+                assert_eq!(extract(&pixels, &options).unwrap(), payload);
+                let changed = (0..pixels.data().len())
+                    .filter(|&index| pixels.data()[index] != original.data()[index])
+                    .count();
+                assert_eq!(modified, changed);
+                // At most one change per block
+                let blocks = ((capacity + 4) * 8).div_ceil(matrix_bits as usize);
+                assert!(
+                    modified <= blocks,
+                    "{} bits: {} > {}",
+                    matrix_bits,
+                    modified,
+                    blocks
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_embedding_changes_fewer_samples() {
+        let payload = vec![0x5a; 100];
+        let modified = |matrix_bits| {
+            let mut pixels = noise_pixels(64, 64, 8, ColorType::Rgb);
+            let options = LsbOptions {
+                matrix_bits,
+                ..LsbOptions::default()
+            };
+            embed(&mut pixels, &payload, &options).unwrap()
+        };
+        // One bit per sample changes about half of the 832 samples used, a block of 7 samples
+        // changes one of them 7 times out of 8
+        assert!(modified(1) > 300);
+        assert!(modified(3) < 260);
+        assert!(modified(3) > modified(5));
+    }
+
+    #[test]
+    fn test_matrix_capacity() {
+        let ihdr = Ihdr::new(64, 64, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let options = |matrix_bits| LsbOptions {
+            matrix_bits,
+            ..LsbOptions::default()
+        };
+        // 12288 samples make 1755 blocks of 7, each holding 3 bits
+        assert_eq!(capacity(&ihdr, &options(3)).unwrap(), 1755 * 3 / 8 - 4);
+        assert_eq!(capacity(&ihdr, &options(1)).unwrap(), 12288 / 8 - 4);
+        assert!(matches!(
+            capacity(&ihdr, &options(0)),
+            Err(PngError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            capacity(&ihdr, &options(9)),
+            Err(PngError::InvalidArgument(_))
+        ));
+
+        let mut pixels = noise_pixels(64, 64, 8, ColorType::Rgb);
+        let wrong_rate = options(2);
+        embed(&mut pixels, b"secret message", &options(4)).unwrap();
+        assert!(
+            !matches!(extract(&pixels, &wrong_rate), Ok(payload) if payload == b"secret message")
+        );
+    }
+
+    #[test]
+    fn test_extract_without_key_or_with_wrong_key() {
+        let mut pixels = noise_pixels(40, 40, 8, ColorType::Rgb);
+        embed(&mut pixels, b"secret message", &keyed("right key")).unwrap();
+        for options in [LsbOptions::default(), keyed("wrong key")] {
+            assert!(
+                !matches!(extract(&pixels, &options), Ok(payload) if payload == b"secret message")
+            );
+        }
+    }
+
+    #[test]
+    fn test_two_bits_per_sample() {
+        let two_bits = |options: LsbOptions| LsbOptions {
+            bits_per_sample: 2,
+            ..options
+        };
+        let ihdr = Ihdr::new(20, 10, 8, ColorType::Rgb, Interlace::None).unwrap();
+        // 600 samples hold 1200 bits
+        assert_eq!(
+            capacity(&ihdr, &two_bits(LsbOptions::default())).unwrap(),
+            150 - 4
+        );
+        assert!(matches!(
+            capacity(
+                &ihdr,
+                &LsbOptions {
+                    bits_per_sample: 3,
+                    ..LsbOptions::default()
+                }
+            ),
+            Err(PngError::InvalidArgument(_))
+        ));
+
+        for (bit_depth, color_type) in [(8, ColorType::Rgb), (16, ColorType::Rgba)] {
+            for options in [LsbOptions::default(), keyed("key")] {
+                let options = two_bits(options);
+                let mut pixels = noise_pixels(20, 10, bit_depth, color_type);
+                let original = pixels.clone();
+                let payload: Vec<u8> = (0..capacity(pixels.ihdr(), &options).unwrap())
+                    .map(|byte| (byte * 13) as u8)
+                    .collect();
+                let modified = embed(&mut pixels, &payload, &options).unwrap();
+                assert_eq!(extract(&pixels, &options).unwrap(), payload);
+
+                let mut changed = 0;
+                for y in 0..10 {
+                    for x in 0..20 {
+                        for channel in 0..color_type.samples_per_pixel() {
+                            let before = original.sample(x, y, channel);
+                            let after = pixels.sample(x, y, channel);
+                            assert_eq!(before | 3, after | 3);
+                            changed += usize::from(before != after);
+                        }
+                    }
+                }
+                assert_eq!(modified, changed);
+            }
+        }
+    }
+}
@@ -1,17 +1,19 @@
+use std::process::ExitCode;
+
 use args::Args;
 use clap::Parser;
 use commands::execute_command;
 
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Args::parse();
-    Ok(execute_command(cli.commands)?)
+    match execute_command(cli.commands) {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,174 @@
+use std::path::Path;
+
+use crate::{
+    byte_reader::ByteReader,
+    error::{PngError, Result},
+};
+
+// File payload layout:
+// magic (4) | version (1) | file name length (2) | file name (UTF-8) | MIME type length (1) | MIME type | file data
+// An empty file name or MIME type means it is unknown.
+const MAGIC: [u8; 4] = *b"PMFL";
+const VERSION: u8 = 1;
+
+/// An embedded file together with the metadata needed to restore it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePayload {
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl FilePayload {
+    /// Builds a payload for the file at `path`, guessing its MIME type from the extension
+    pub fn from_path(path: &Path, data: Vec<u8>) -> FilePayload {
+        FilePayload {
+            file_name: path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .map(str::to_string),
+            mime_type: Some(mime_type_for(path).to_string()),
+            data,
+        }
+    }
+
+    /// Bytes `to_bytes` adds to the file data
+    pub fn header_length(&self) -> usize {
+        8 + self.file_name.as_deref().map_or(0, str::len)
+            + self.mime_type.as_deref().map_or(0, str::len)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let file_name = self.file_name.as_deref().unwrap_or_default();
+        let mime_type = self.mime_type.as_deref().unwrap_or_default();
+        if file_name.len() > u16::MAX as usize || mime_type.len() > u8::MAX as usize {
+            return Err(PngError::MalformedPayload(
+                "file name or MIME type is too long".to_string(),
+            ));
+        }
+
+        let mut bytes = Vec::with_capacity(self.header_length() + self.data.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(file_name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(file_name.as_bytes());
+        bytes.push(mime_type.len() as u8);
+        bytes.extend_from_slice(mime_type.as_bytes());
+        bytes.extend_from_slice(&self.data);
+        Ok(bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<FilePayload> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(PngError::MalformedPayload(
+                "data is not a file payload".to_string(),
+            ));
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(PngError::MalformedPayload(format!(
+                "unsupported file payload version {}",
+                version
+            )));
+        }
+        let file_name_length = u16::from_be_bytes(reader.read_array()?) as usize;
+        let file_name = std::str::from_utf8(reader.read_bytes(file_name_length)?)?;
+        let mime_type_length = reader.read_u8()? as usize;
+        let mime_type = std::str::from_utf8(reader.read_bytes(mime_type_length)?)?;
+
+        Ok(FilePayload {
+            file_name: (!file_name.is_empty()).then(|| file_name.to_string()),
+            mime_type: (!mime_type.is_empty()).then(|| mime_type.to_string()),
+            data: reader.rest().to_vec(),
+        })
+    }
+
+    /// The stored file name reduced to its last component, so it can't point outside
+    /// the directory it is extracted to
+    pub fn safe_file_name(&self) -> Option<&str> {
+        let file_name = Path::new(self.file_name.as_deref()?)
+            .file_name()?
+            .to_str()?;
+        (file_name != "." && file_name != "..").then_some(file_name)
+    }
+}
+
+/// MIME type for common file extensions, `application/octet-stream` for anything else
+pub fn mime_type_for(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let payload = FilePayload::from_path(Path::new("docs/report.PDF"), vec![0, 159, 255]);
+        assert_eq!(payload.file_name.as_deref(), Some("report.PDF"));
+        assert_eq!(payload.mime_type.as_deref(), Some("application/pdf"));
+
+        let bytes = payload.to_bytes().unwrap();
+        assert_eq!(bytes.len(), payload.header_length() + 3);
+        assert_eq!(FilePayload::parse(&bytes).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_unknown_metadata() {
+        let payload = FilePayload {
+            file_name: None,
+            mime_type: None,
+            data: b"from stdin".to_vec(),
+        };
+        let bytes = payload.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 8 + b"from stdin".len());
+        assert_eq!(FilePayload::parse(&bytes).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        let mut payload = FilePayload::from_path(Path::new("secret.txt"), Vec::new());
+        assert_eq!(payload.safe_file_name(), Some("secret.txt"));
+
+        payload.file_name = Some("../../etc/passwd".to_string());
+        assert_eq!(payload.safe_file_name(), Some("passwd"));
+
+        payload.file_name = Some("..".to_string());
+        assert_eq!(payload.safe_file_name(), None);
+    }
+
+    #[test]
+    fn test_truncated_payload() {
+        let payload = FilePayload::from_path(Path::new("secret.txt"), Vec::new());
+        let bytes = payload.to_bytes().unwrap();
+        for length in 0..bytes.len() {
+            assert!(FilePayload::parse(&bytes[..length]).is_err());
+        }
+    }
+}
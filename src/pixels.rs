@@ -0,0 +1,725 @@
+use crate::{
+    compression,
+    error::{PngError, Result},
+    ihdr::{ColorType, Ihdr, Interlace},
+};
+
+/// Largest decoded image `Png::pixels` accepts, 1 GiB
+pub const MAX_PIXEL_DATA_LENGTH: usize = 1024 * 1024 * 1024;
+
+// Adam7 passes as (first column, first row, column step, row step)
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Scanline filter types, stored in the byte in front of every filtered row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+
+    pub fn from_byte(byte: u8) -> Result<FilterType> {
+        FilterType::ALL
+            .get(byte as usize)
+            .copied()
+            .ok_or_else(|| PngError::InvalidImageData(format!("unknown filter type {}", byte)))
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Decoded image samples, laid out as described by the image header.
+///
+/// Rows are stored top to bottom without filter bytes or interlacing. Within a row, samples keep
+/// their PNG encoding: depths below 8 bits are packed most significant bit first, 16-bit samples
+/// are big-endian, and indexed images hold palette indices rather than colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelBuffer {
+    ihdr: Ihdr,
+    data: Vec<u8>,
+}
+
+impl PixelBuffer {
+    /// Wraps raw samples, which must be exactly `height` rows of `row_length` bytes
+    pub fn new(ihdr: Ihdr, data: Vec<u8>) -> Result<PixelBuffer> {
+        let expected = image_length(&ihdr, ihdr.width as usize, ihdr.height as usize)?;
+        if data.len() != expected {
+            return Err(PngError::InvalidImageData(format!(
+                "expected {} bytes of pixel data, got {}",
+                expected,
+                data.len()
+            )));
+        }
+        Ok(PixelBuffer { ihdr, data })
+    }
+
+    pub fn ihdr(&self) -> &Ihdr {
+        &self.ihdr
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Bytes taken by one row of the image
+    pub fn row_length(&self) -> usize {
+        row_length(&self.ihdr, self.ihdr.width as usize)
+    }
+
+    pub fn row(&self, y: usize) -> &[u8] {
+        let row_length = self.row_length();
+        &self.data[y * row_length..(y + 1) * row_length]
+    }
+
+    /// Value of one sample (channel) of the pixel at column `x` and row `y`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel or channel is outside the image.
+    pub fn sample(&self, x: usize, y: usize, channel: usize) -> u16 {
+        let samples_per_pixel = self.ihdr.color_type.samples_per_pixel();
+        assert!(
+            x < self.ihdr.width as usize && channel < samples_per_pixel,
+            "sample is outside the image"
+        );
+        let row = self.row(y);
+        let index = x * samples_per_pixel + channel;
+        match self.ihdr.bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            bit_depth => {
+                let bit_depth = bit_depth as usize;
+                let bit = index * bit_depth;
+                let shift = 8 - bit_depth - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+            }
+        }
+    }
+}
+
+/// Bytes in one row of `width` pixels, without the filter byte
+pub(crate) fn row_length(ihdr: &Ihdr, width: usize) -> usize {
+    (width * ihdr.bits_per_pixel()).div_ceil(8)
+}
+
+/// Distance in bytes between a byte and the matching byte of the previous pixel, at least 1
+pub(crate) fn filter_distance(ihdr: &Ihdr) -> usize {
+    ihdr.bits_per_pixel().div_ceil(8)
+}
+
+/// A sub-image the image data is stored as: the whole image, or one of the seven Adam7 passes.
+/// It covers every `column_step`th pixel from `first_column` in every `row_step`th row from `first_row`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Pass {
+    pub(crate) first_column: usize,
+    pub(crate) first_row: usize,
+    pub(crate) column_step: usize,
+    pub(crate) row_step: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+}
+
+impl Pass {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+pub(crate) fn passes(ihdr: &Ihdr) -> Vec<Pass> {
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+    let pattern: &[(usize, usize, usize, usize)] = match ihdr.interlace {
+        Interlace::None => &[(0, 0, 1, 1)],
+        Interlace::Adam7 => &ADAM7_PASSES,
+    };
+    pattern
+        .iter()
+        .map(|&(first_column, first_row, column_step, row_step)| Pass {
+            first_column,
+            first_row,
+            column_step,
+            row_step,
+            width: width.saturating_sub(first_column).div_ceil(column_step),
+            height: height.saturating_sub(first_row).div_ceil(row_step),
+        })
+        .collect()
+}
+
+fn image_length(ihdr: &Ihdr, width: usize, height: usize) -> Result<usize> {
+    width
+        .checked_mul(ihdr.bits_per_pixel())
+        .map(|bits| bits.div_ceil(8))
+        .and_then(|row_length| row_length.checked_mul(height))
+        .filter(|&length| length <= MAX_PIXEL_DATA_LENGTH)
+        .ok_or_else(|| {
+            PngError::InvalidImageData(format!(
+                "a {} x {} image is larger than {} bytes",
+                ihdr.width, ihdr.height, MAX_PIXEL_DATA_LENGTH
+            ))
+        })
+}
+
+/// Length of the inflated image data: every pass's rows, each with its filter byte
+pub(crate) fn filtered_length(ihdr: &Ihdr) -> Result<usize> {
+    // The filtered data is never larger than the pixels plus one byte per row of each pass
+    image_length(ihdr, ihdr.width as usize, ihdr.height as usize)?;
+    Ok(passes(ihdr)
+        .iter()
+        .filter(|pass| !pass.is_empty())
+        .map(|pass| (row_length(ihdr, pass.width) + 1) * pass.height)
+        .sum())
+}
+
+/// Inflates the concatenated `IDAT` data and reverses filtering and interlacing
+pub(crate) fn decode(ihdr: &Ihdr, idat: &[u8]) -> Result<PixelBuffer> {
+    let filtered_length = filtered_length(ihdr)?;
+    let filtered =
+        compression::zlib_decompress(idat, filtered_length).map_err(|err| match err {
+            PngError::ExpansionLimitExceeded(_) => PngError::InvalidImageData(
+                "image data is longer than the header allows".to_string(),
+            ),
+            err => err,
+        })?;
+    if filtered.len() < filtered_length {
+        return Err(PngError::InvalidImageData(format!(
+            "image data is truncated: expected {} bytes, got {}",
+            filtered_length,
+            filtered.len()
+        )));
+    }
+
+    let image_row_length = row_length(ihdr, ihdr.width as usize);
+    let mut data = vec![0; image_row_length * ihdr.height as usize];
+    let distance = filter_distance(ihdr);
+    let bits_per_pixel = ihdr.bits_per_pixel();
+    let mut offset = 0;
+    for pass in passes(ihdr) {
+        if pass.is_empty() {
+            continue;
+        }
+        let pass_row_length = row_length(ihdr, pass.width);
+        let mut previous = vec![0; pass_row_length];
+        let mut current = vec![0; pass_row_length];
+        for pass_y in 0..pass.height {
+            let filter_type = FilterType::from_byte(filtered[offset])?;
+            current.copy_from_slice(&filtered[offset + 1..offset + 1 + pass_row_length]);
+            offset += pass_row_length + 1;
+            unfilter(filter_type, distance, &previous, &mut current);
+
+            let y = pass.first_row + pass_y * pass.row_step;
+            let image_row = &mut data[y * image_row_length..][..image_row_length];
+            if pass.column_step == 1 {
+                image_row.copy_from_slice(&current);
+            } else {
+                for pass_x in 0..pass.width {
+                    let x = pass.first_column + pass_x * pass.column_step;
+                    copy_pixel(&current, pass_x, image_row, x, bits_per_pixel);
+                }
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+    }
+
+    PixelBuffer::new(*ihdr, data)
+}
+
+/// How `encode` picks the filter for each row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Every row uses the same filter
+    Fixed(FilterType),
+    /// Each row uses the filter whose output has the smallest sum of absolute values, which tends
+    /// to compress best. Indexed and sub-byte images use no filter, as the PNG specification advises.
+    Adaptive,
+}
+
+/// Settings for turning a `PixelBuffer` into image data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub filter_strategy: FilterStrategy,
+    /// zlib compression level, from 0 (store only) to 9 (smallest output)
+    pub compression_level: u32,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            filter_strategy: FilterStrategy::Adaptive,
+            compression_level: 6,
+        }
+    }
+}
+
+/// Filters and compresses `pixels` into the zlib stream stored in the `IDAT` chunks,
+/// interlacing it when the header asks for Adam7
+pub(crate) fn encode(pixels: &PixelBuffer, options: EncodeOptions) -> Result<Vec<u8>> {
+    if options.compression_level > 9 {
+        return Err(PngError::InvalidImageData(format!(
+            "compression level must be between 0 and 9, not {}",
+            options.compression_level
+        )));
+    }
+    let ihdr = pixels.ihdr();
+    let filter_strategy = match options.filter_strategy {
+        FilterStrategy::Adaptive if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 => {
+            FilterStrategy::Fixed(FilterType::None)
+        }
+        filter_strategy => filter_strategy,
+    };
+
+    let image_row_length = pixels.row_length();
+    let distance = filter_distance(ihdr);
+    let bits_per_pixel = ihdr.bits_per_pixel();
+    let mut filtered = Vec::with_capacity(filtered_length(ihdr)?);
+    for pass in passes(ihdr) {
+        if pass.is_empty() {
+            continue;
+        }
+        let pass_row_length = row_length(ihdr, pass.width);
+        let mut previous = vec![0; pass_row_length];
+        let mut current = vec![0; pass_row_length];
+        let mut candidate = vec![0; pass_row_length];
+        let mut best = vec![0; pass_row_length];
+        for pass_y in 0..pass.height {
+            let y = pass.first_row + pass_y * pass.row_step;
+            let image_row = &pixels.data()[y * image_row_length..][..image_row_length];
+            if pass.column_step == 1 {
+                current.copy_from_slice(image_row);
+            } else {
+                for pass_x in 0..pass.width {
+                    let x = pass.first_column + pass_x * pass.column_step;
+                    copy_pixel(image_row, x, &mut current, pass_x, bits_per_pixel);
+                }
+            }
+
+            let filter_type = match filter_strategy {
+                FilterStrategy::Fixed(filter_type) => {
+                    filter(filter_type, distance, &previous, &current, &mut best);
+                    filter_type
+                }
+                FilterStrategy::Adaptive => {
+                    let mut best_filter = (FilterType::None, u64::MAX);
+                    for filter_type in FilterType::ALL {
+                        filter(filter_type, distance, &previous, &current, &mut candidate);
+                        // Filtered bytes are read as signed, so small steps either way score low
+                        let score = candidate
+                            .iter()
+                            .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                            .sum();
+                        if score < best_filter.1 {
+                            best_filter = (filter_type, score);
+                            std::mem::swap(&mut best, &mut candidate);
+                        }
+                    }
+                    best_filter.0
+                }
+            };
+            filtered.push(filter_type.to_byte());
+            filtered.extend_from_slice(&best);
+            std::mem::swap(&mut previous, &mut current);
+        }
+    }
+
+    compression::zlib_compress_with_level(&filtered, options.compression_level)
+}
+
+/// Applies `filter_type` to `row` given the previous unfiltered row, writing the result to `output`
+fn filter(
+    filter_type: FilterType,
+    distance: usize,
+    previous: &[u8],
+    row: &[u8],
+    output: &mut [u8],
+) {
+    for i in 0..row.len() {
+        let (left, upper_left) = if i >= distance {
+            (row[i - distance], previous[i - distance])
+        } else {
+            (0, 0)
+        };
+        let prediction = match filter_type {
+            FilterType::None => 0,
+            FilterType::Sub => left,
+            FilterType::Up => previous[i],
+            FilterType::Average => ((left as u16 + previous[i] as u16) / 2) as u8,
+            FilterType::Paeth => paeth_predictor(left, previous[i], upper_left),
+        };
+        output[i] = row[i].wrapping_sub(prediction);
+    }
+}
+
+/// Reverses `filter_type` on `row` in place, given the already unfiltered previous row
+fn unfilter(filter_type: FilterType, distance: usize, previous: &[u8], row: &mut [u8]) {
+    match filter_type {
+        FilterType::None => {}
+        FilterType::Sub => {
+            for i in distance..row.len() {
+                row[i] = row[i].wrapping_add(row[i - distance]);
+            }
+        }
+        FilterType::Up => {
+            for (byte, above) in row.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(*above);
+            }
+        }
+        FilterType::Average => {
+            for i in 0..row.len() {
+                let left = if i >= distance { row[i - distance] } else { 0 };
+                let average = ((left as u16 + previous[i] as u16) / 2) as u8;
+                row[i] = row[i].wrapping_add(average);
+            }
+        }
+        FilterType::Paeth => {
+            for i in 0..row.len() {
+                let (left, upper_left) = if i >= distance {
+                    (row[i - distance], previous[i - distance])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth_predictor(left, previous[i], upper_left));
+            }
+        }
+    }
+}
+
+/// Whichever of the left, above and upper left bytes is closest to `left + above - upper_left`
+pub(crate) fn paeth_predictor(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_above = (estimate - above as i16).abs();
+    let distance_upper_left = (estimate - upper_left as i16).abs();
+    if distance_left <= distance_above && distance_left <= distance_upper_left {
+        left
+    } else if distance_above <= distance_upper_left {
+        above
+    } else {
+        upper_left
+    }
+}
+
+/// Copies pixel `from_x` of `from` to pixel `to_x` of `to`, including pixels smaller than a byte
+pub(crate) fn copy_pixel(from: &[u8], from_x: usize, to: &mut [u8], to_x: usize, bits: usize) {
+    if bits >= 8 {
+        let bytes = bits / 8;
+        to[to_x * bytes..(to_x + 1) * bytes].copy_from_slice(&from[from_x * bytes..][..bytes]);
+    } else {
+        let mask = (1u8 << bits) - 1;
+        let from_shift = 8 - bits - (from_x * bits) % 8;
+        let to_shift = 8 - bits - (to_x * bits) % 8;
+        let value = (from[from_x * bits / 8] >> from_shift) & mask;
+        let to_byte = &mut to[to_x * bits / 8];
+        *to_byte = (*to_byte & !(mask << to_shift)) | (value << to_shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        test_util::{pngsuite, XorShift},
+        Chunk, ChunkType, Png,
+    };
+
+    const COLOR_TYPES: [ColorType; 5] = [
+        ColorType::Grayscale,
+        ColorType::Rgb,
+        ColorType::Indexed,
+        ColorType::GrayscaleAlpha,
+        ColorType::Rgba,
+    ];
+
+    /// Deterministic noise with the padding bits at the end of each row cleared
+    fn test_pixels(ihdr: &Ihdr) -> Vec<u8> {
+        let row_length = row_length(ihdr, ihdr.width as usize);
+        let padding_bits = row_length * 8 - ihdr.width as usize * ihdr.bits_per_pixel();
+        let mut rng = XorShift(0x2545_f491 ^ ihdr.width ^ (ihdr.height << 8));
+        let mut pixels = Vec::with_capacity(row_length * ihdr.height as usize);
+        for _ in 0..ihdr.height {
+            for _ in 0..row_length {
+                pixels.push((rng.next() >> 24) as u8);
+            }
+            *pixels.last_mut().unwrap() &= 0xff << padding_bits;
+        }
+        pixels
+    }
+
+    fn filter_row(
+        filter_type: FilterType,
+        distance: usize,
+        previous: &[u8],
+        row: &[u8],
+    ) -> Vec<u8> {
+        (0..row.len())
+            .map(|i| {
+                let left = if i >= distance { row[i - distance] } else { 0 };
+                let upper_left = if i >= distance {
+                    previous[i - distance]
+                } else {
+                    0
+                };
+                let prediction = match filter_type {
+                    FilterType::None => 0,
+                    FilterType::Sub => left,
+                    FilterType::Up => previous[i],
+                    FilterType::Average => ((left as u16 + previous[i] as u16) / 2) as u8,
+                    FilterType::Paeth => paeth_predictor(left, previous[i], upper_left),
+                };
+                row[i].wrapping_sub(prediction)
+            })
+            .collect()
+    }
+
+    /// Builds a PNG the slow, obvious way, cycling through every filter type row by row and
+    /// splitting the image data across two IDAT chunks
+    fn encode_test_png(ihdr: &Ihdr, pixels: &[u8]) -> Vec<u8> {
+        let image_row_length = row_length(ihdr, ihdr.width as usize);
+        let distance = filter_distance(ihdr);
+        let mut filtered = Vec::new();
+        let mut filter_types = FilterType::ALL.iter().cycle();
+        for pass in passes(ihdr).into_iter().filter(|pass| !pass.is_empty()) {
+            let mut previous = vec![0; row_length(ihdr, pass.width)];
+            for pass_y in 0..pass.height {
+                let y = pass.first_row + pass_y * pass.row_step;
+                let image_row = &pixels[y * image_row_length..][..image_row_length];
+                let mut row = vec![0; previous.len()];
+                for pass_x in 0..pass.width {
+                    let x = pass.first_column + pass_x * pass.column_step;
+                    copy_pixel(image_row, x, &mut row, pass_x, ihdr.bits_per_pixel());
+                }
+                let filter_type = *filter_types.next().unwrap();
+                filtered.push(filter_type.to_byte());
+                filtered.extend(filter_row(filter_type, distance, &previous, &row));
+                previous = row;
+            }
+        }
+        let idat = compression::zlib_compress(&filtered).unwrap();
+        let (first_idat, second_idat) = idat.split_at(idat.len() / 2);
+
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::try_new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).unwrap()
+        };
+        let mut chunks = vec![ihdr.to_chunk()];
+        if ihdr.color_type == ColorType::Indexed {
+            let palette: Vec<u8> = (0..=255)
+                .flat_map(|index| [index, 255 - index, index / 2])
+                .collect();
+            chunks.push(chunk("PLTE", &palette));
+        }
+        chunks.push(chunk("IDAT", first_idat));
+        chunks.push(chunk("IDAT", second_idat));
+        chunks.push(chunk("IEND", &[]));
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    /// Decodes with the `png` crate as an independent reference, without any transformation
+    fn reference_decode(bytes: &[u8]) -> Vec<u8> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        buffer.truncate(info.buffer_size());
+        buffer
+    }
+
+    #[test]
+    fn test_all_formats_match_reference_decoder() {
+        for color_type in COLOR_TYPES {
+            for &bit_depth in color_type.allowed_bit_depths() {
+                for interlace in [Interlace::None, Interlace::Adam7] {
+                    for (width, height) in [(1, 1), (3, 2), (7, 5), (33, 17)] {
+                        let ihdr =
+                            Ihdr::new(width, height, bit_depth, color_type, interlace).unwrap();
+                        let pixels = test_pixels(&ihdr);
+                        let bytes = encode_test_png(&ihdr, &pixels);
+
+                        let decoded = Png::try_from(&bytes[..]).unwrap().pixels().unwrap();
+                        assert_eq!(decoded.data(), pixels, "{:?}", ihdr);
+                        assert_eq!(reference_decode(&bytes), pixels, "{:?}", ihdr);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pngsuite_conformance() {
+        let valid_files: Vec<(String, Vec<u8>)> = pngsuite()
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with('x'))
+            .collect();
+        assert!(valid_files.len() > 150);
+        for (name, bytes) in valid_files {
+            let png = Png::try_from(&bytes[..]).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            let pixels = png
+                .pixels()
+                .unwrap_or_else(|err| panic!("{}: {}", name, err));
+            assert_eq!(pixels.data(), reference_decode(&bytes), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_pngsuite_corrupted_files_are_rejected() {
+        // Files starting with `x` have a broken signature, header, checksum or image data
+        for (name, bytes) in pngsuite()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with('x'))
+        {
+            let result = Png::try_from(&bytes[..]).and_then(|png| png.pixels());
+            assert!(result.is_err(), "{} should be rejected", name);
+        }
+    }
+
+    fn test_palette() -> Vec<[u8; 3]> {
+        (0..=255)
+            .map(|index| [index, 255 - index, index / 2])
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_round_trip_all_formats_and_strategies() {
+        let strategies = FilterType::ALL
+            .map(FilterStrategy::Fixed)
+            .into_iter()
+            .chain([FilterStrategy::Adaptive]);
+        for filter_strategy in strategies {
+            for color_type in COLOR_TYPES {
+                for &bit_depth in color_type.allowed_bit_depths() {
+                    for interlace in [Interlace::None, Interlace::Adam7] {
+                        let ihdr = Ihdr::new(13, 9, bit_depth, color_type, interlace).unwrap();
+                        let buffer = PixelBuffer::new(ihdr, test_pixels(&ihdr)).unwrap();
+                        let palette = test_palette();
+                        let palette =
+                            (color_type == ColorType::Indexed).then(|| &palette[..1 << bit_depth]);
+                        let options = EncodeOptions {
+                            filter_strategy,
+                            compression_level: 9,
+                        };
+                        let bytes = Png::from_pixels(&buffer, palette, options)
+                            .unwrap()
+                            .as_bytes();
+
+                        let decoded = Png::try_from(&bytes[..]).unwrap().pixels().unwrap();
+                        assert_eq!(decoded, buffer, "{:?} {:?}", filter_strategy, ihdr);
+                        assert_eq!(reference_decode(&bytes), buffer.data());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_adaptive_filter_compresses_gradients_better() {
+        let ihdr = Ihdr::new(256, 256, 8, ColorType::Rgb, Interlace::None).unwrap();
+        let gradient: Vec<u8> = (0..256 * 256)
+            .flat_map(|i| [(i % 256) as u8, (i / 256) as u8, (i % 256 + i / 256) as u8])
+            .collect();
+        let buffer = PixelBuffer::new(ihdr, gradient).unwrap();
+        let encoded_length = |filter_strategy| {
+            let options = EncodeOptions {
+                filter_strategy,
+                ..EncodeOptions::default()
+            };
+            encode(&buffer, options).unwrap().len()
+        };
+
+        assert!(
+            encoded_length(FilterStrategy::Adaptive)
+                < encoded_length(FilterStrategy::Fixed(FilterType::None))
+        );
+    }
+
+    #[test]
+    fn test_compression_level() {
+        let ihdr = Ihdr::new(64, 64, 8, ColorType::Grayscale, Interlace::None).unwrap();
+        let buffer = PixelBuffer::new(ihdr, vec![7; 64 * 64]).unwrap();
+        let encoded_length = |compression_level| {
+            let options = EncodeOptions {
+                compression_level,
+                ..EncodeOptions::default()
+            };
+            encode(&buffer, options).map(|encoded| encoded.len())
+        };
+
+        assert!(encoded_length(9).unwrap() < encoded_length(0).unwrap());
+        assert!(encoded_length(10).is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let ihdr = Ihdr::new(3, 1, 2, ColorType::Grayscale, Interlace::None).unwrap();
+        let buffer = PixelBuffer::new(ihdr, vec![0b01_10_11_00]).unwrap();
+        assert_eq!(
+            (0..3).map(|x| buffer.sample(x, 0, 0)).collect::<Vec<u16>>(),
+            vec![1, 2, 3]
+        );
+
+        let ihdr = Ihdr::new(1, 2, 16, ColorType::GrayscaleAlpha, Interlace::None).unwrap();
+        let buffer = PixelBuffer::new(ihdr, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(buffer.sample(0, 1, 1), 0x0708);
+    }
+
+    #[test]
+    fn test_invalid_filter_type() {
+        let ihdr = Ihdr::new(2, 1, 8, ColorType::Grayscale, Interlace::None).unwrap();
+        let idat = compression::zlib_compress(&[5, 1, 2]).unwrap();
+        assert!(matches!(
+            decode(&ihdr, &idat),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_data_length() {
+        let ihdr = Ihdr::new(2, 2, 8, ColorType::Grayscale, Interlace::None).unwrap();
+        let truncated = compression::zlib_compress(&[0, 1, 2, 0, 3]).unwrap();
+        assert!(matches!(
+            decode(&ihdr, &truncated),
+            Err(PngError::InvalidImageData(_))
+        ));
+        let too_long = compression::zlib_compress(&[0, 1, 2, 0, 3, 4, 0]).unwrap();
+        assert!(matches!(
+            decode(&ihdr, &too_long),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_header_is_rejected_before_inflating() {
+        let ihdr = Ihdr::new(1 << 20, 1 << 20, 16, ColorType::Rgba, Interlace::None).unwrap();
+        assert!(matches!(
+            decode(&ihdr, &[]),
+            Err(PngError::InvalidImageData(_))
+        ));
+    }
+}
@@ -1,30 +1,98 @@
-use std::{fmt::Display, str::FromStr};
-
-use crate::{chunk::Chunk, chunk_type::ChunkType};
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::PngError,
+    ihdr::{ColorType, Ihdr},
+    pixels::{self, EncodeOptions, PixelBuffer},
+    reader::PngReader,
+    writer::PngWriter,
+};
+
+// Image data is split into chunks of this size, which every decoder handles comfortably
+const IDAT_CHUNK_LENGTH: usize = 64 * 1024;
 
 #[derive(Debug)]
 pub struct Png {
     signature: Vec<Chunk>,
+    /// Bytes after `IEND`, kept as they are
+    trailing_data: Vec<u8>,
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
-        Png { signature: chunks }
+        Png {
+            signature: chunks,
+            trailing_data: Vec::new(),
+        }
+    }
+    /// Reads a whole PNG from a stream, see `PngReader` to process chunks one at a time
+    pub fn read_from<R: Read>(reader: R) -> Result<Png, PngError> {
+        let mut png_reader = PngReader::new(reader)?;
+        let chunks = png_reader
+            .by_ref()
+            .collect::<Result<Vec<Chunk>, PngError>>()?;
+        let mut trailing_data = Vec::new();
+        png_reader.into_inner().read_to_end(&mut trailing_data)?;
+        Ok(Png {
+            signature: chunks,
+            trailing_data,
+        })
     }
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.signature.push(chunk);
     }
-    pub fn remove_first_chunk(
-        &mut self,
-        chunk_type: &str,
-    ) -> Result<Chunk, Box<dyn std::error::Error>> {
-        let position = self
+    /// Inserts `chunk` so that it ends up at `index`, shifting the following chunks back
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<(), PngError> {
+        if index > self.signature.len() {
+            return Err(PngError::IndexOutOfBounds {
+                index,
+                length: self.signature.len(),
+            });
+        }
+        self.signature.insert(index, chunk);
+        Ok(())
+    }
+    /// Inserts `chunk` right before the first chunk of type `chunk_type`
+    pub fn insert_before(&mut self, chunk_type: &str, chunk: Chunk) -> Result<(), PngError> {
+        let position = self.first_position(chunk_type)?;
+        self.insert_chunk_at(position, chunk)
+    }
+    /// Inserts `chunk` right after the first chunk of type `chunk_type`
+    pub fn insert_after(&mut self, chunk_type: &str, chunk: Chunk) -> Result<(), PngError> {
+        let position = self.first_position(chunk_type)?;
+        self.insert_chunk_at(position + 1, chunk)
+    }
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, PngError> {
+        let position = self.first_position(chunk_type)?;
+        let removed_chunk = self.signature.remove(position);
+        Ok(removed_chunk)
+    }
+    /// Removes every chunk of type `chunk_type`, returning them in file order
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Result<Vec<Chunk>, PngError> {
+        let chunk_type_to_remove = ChunkType::from_str(chunk_type)?;
+        let (removed_chunks, kept_chunks): (Vec<Chunk>, Vec<Chunk>) = self
             .signature
+            .drain(..)
+            .partition(|chunk| *chunk.chunk_type() == chunk_type_to_remove);
+        self.signature = kept_chunks;
+        if removed_chunks.is_empty() {
+            return Err(PngError::ChunkNotFound(chunk_type.to_string()));
+        }
+        Ok(removed_chunks)
+    }
+    fn first_position(&self, chunk_type: &str) -> Result<usize, PngError> {
+        let chunk_type_to_find = ChunkType::from_str(chunk_type)?;
+        self.signature
             .iter()
-            .position(|chunk| *chunk.chunk_type() == ChunkType::from_str(chunk_type).unwrap());
-        let removed_chunk = self.signature.remove(position.unwrap());
-        Ok(removed_chunk)
+            .position(|chunk| *chunk.chunk_type() == chunk_type_to_find)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))
     }
     pub fn header(&self) -> &[u8; 8] {
         &Png::STANDARD_HEADER
@@ -32,36 +100,172 @@ impl Png {
     pub fn chunks(&self) -> &[Chunk] {
         &self.signature
     }
+    /// Bytes after `IEND`, which decoders ignore. Empty for most files.
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+    pub fn set_trailing_data(&mut self, trailing_data: Vec<u8>) {
+        self.trailing_data = trailing_data;
+    }
+    /// Parses and validates the image header, which must be the first chunk
+    pub fn ihdr(&self) -> Result<Ihdr, PngError> {
+        let first_chunk = self
+            .signature
+            .first()
+            .ok_or_else(|| PngError::ChunkNotFound("IHDR".to_string()))?;
+        Ihdr::try_from(first_chunk)
+    }
+    /// Encodes `pixels` into a complete PNG: `IHDR`, `PLTE` when a palette is given, the filtered
+    /// and compressed `IDAT` data and `IEND`.
+    ///
+    /// Indexed images need a palette that covers every index they use. RGB and RGBA images may
+    /// carry a suggested palette, grayscale images can't have one.
+    pub fn from_pixels(
+        pixels: &PixelBuffer,
+        palette: Option<&[[u8; 3]]>,
+        options: EncodeOptions,
+    ) -> Result<Png, PngError> {
+        let ihdr = pixels.ihdr();
+        let mut chunks = vec![ihdr.to_chunk()];
+        match (ihdr.color_type, palette) {
+            (ColorType::Indexed, None) => {
+                return Err(PngError::InvalidImageData(
+                    "indexed images need a palette".to_string(),
+                ))
+            }
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, Some(_)) => {
+                return Err(PngError::InvalidImageData(
+                    "grayscale images can't have a palette".to_string(),
+                ))
+            }
+            (_, Some(palette)) => {
+                if palette.is_empty() || palette.len() > 256 {
+                    return Err(PngError::InvalidImageData(format!(
+                        "a palette holds 1 to 256 colors, not {}",
+                        palette.len()
+                    )));
+                }
+                if ihdr.color_type == ColorType::Indexed
+                    && (0..ihdr.height as usize)
+                        .flat_map(|y| (0..ihdr.width as usize).map(move |x| (x, y)))
+                        .any(|(x, y)| pixels.sample(x, y, 0) as usize >= palette.len())
+                {
+                    return Err(PngError::InvalidImageData(format!(
+                        "pixel uses a color index past the {} palette entries",
+                        palette.len()
+                    )));
+                }
+                chunks.push(Chunk::try_new(
+                    ChunkType::from_str("PLTE")?,
+                    palette.concat(),
+                )?);
+            }
+            (_, None) => {}
+        }
+
+        chunks.extend(idat_chunks(pixels, options)?);
+        chunks.push(Chunk::try_new(ChunkType::from_str("IEND")?, Vec::new())?);
+        Ok(Png::from_chunks(chunks))
+    }
+    /// Replaces the image data with `pixels`, keeping every other chunk where it is.
+    /// The pixels must have the same header as the image.
+    pub fn replace_pixels(
+        &mut self,
+        pixels: &PixelBuffer,
+        options: EncodeOptions,
+    ) -> Result<(), PngError> {
+        if *pixels.ihdr() != self.ihdr()? {
+            return Err(PngError::InvalidImageData(
+                "the pixels don't match the image header".to_string(),
+            ));
+        }
+        let new_idat = idat_chunks(pixels, options)?;
+        let position = self.first_position("IDAT")?;
+        self.remove_all_chunks("IDAT")?;
+        self.signature.splice(position..position, new_idat);
+        Ok(())
+    }
+    /// Decodes the image: concatenates the IDAT chunks, inflates them and reverses filtering
+    /// and interlacing. Images larger than `pixels::MAX_PIXEL_DATA_LENGTH` are rejected.
+    pub fn pixels(&self) -> Result<PixelBuffer, PngError> {
+        let ihdr = self.ihdr()?;
+        let idat: Vec<u8> = self
+            .chunks_by_type("IDAT")
+            .flat_map(|chunk| chunk.data())
+            .copied()
+            .collect();
+        if idat.is_empty() {
+            return Err(PngError::ChunkNotFound("IDAT".to_string()));
+        }
+        pixels::decode(&ihdr, &idat)
+    }
     pub fn chunk_by_type(&self, chunk_type_str: &str) -> Option<&Chunk> {
-        let chunk_type = ChunkType::from_str(chunk_type_str).unwrap();
+        let chunk_type = ChunkType::from_str(chunk_type_str).ok()?;
         self.signature
             .iter()
             .find(|chunk| *chunk.chunk_type() == chunk_type)
     }
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let bytes_val: Vec<u8> = self
-            .signature
+    /// Iterates over every chunk of type `chunk_type_str`, in file order
+    pub fn chunks_by_type<'a>(&'a self, chunk_type_str: &str) -> impl Iterator<Item = &'a Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type_str).ok();
+        self.signature
             .iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
-
-        let mut new_data: Vec<u8> = Vec::new();
-        new_data.extend(&self.header().to_vec());
-        new_data.extend(&bytes_val);
+            .filter(move |chunk| Some(*chunk.chunk_type()) == chunk_type)
+    }
+    /// Canonical bytes of every critical chunk, in file order, for signing the image content.
+    /// Ancillary chunks are left out so that adding or removing metadata keeps signatures valid.
+    pub fn critical_canonical_bytes(&self) -> Vec<u8> {
+        self.signature
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+            .flat_map(|chunk| chunk.canonical_bytes())
+            .collect()
+    }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let length = self.header().len()
+            + self
+                .signature
+                .iter()
+                .map(|chunk| chunk.length() as usize + Chunk::METADATA_LENGTH)
+                .sum::<usize>()
+            + self.trailing_data.len();
+        let mut new_data: Vec<u8> = Vec::with_capacity(length);
+        self.write_to(&mut new_data)
+            .expect("writing to a Vec never fails");
 
         new_data
     }
+    /// Writes the signature, every chunk and the trailing data directly into `writer`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), PngError> {
+        let mut png_writer = PngWriter::new(writer)?;
+        png_writer.write_chunks(&self.signature)?;
+        png_writer.write_trailing_data(&self.trailing_data)?;
+        png_writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Filters and compresses `pixels` into `IDAT` chunks
+fn idat_chunks(pixels: &PixelBuffer, options: EncodeOptions) -> Result<Vec<Chunk>, PngError> {
+    let idat_type = ChunkType::from_str("IDAT")?;
+    pixels::encode(pixels, options)?
+        .chunks(IDAT_CHUNK_LENGTH)
+        .map(|idat| Chunk::try_new(idat_type, idat.to_vec()))
+        .collect()
 }
 
 impl TryFrom<&[u8]> for Png {
-    type Error = &'static str;
+    type Error = PngError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         if value.len() < Png::STANDARD_HEADER.len() {
-            return Err("Length of string less than Standard Header");
+            return Err(PngError::Truncated {
+                needed: Png::STANDARD_HEADER.len(),
+                available: value.len(),
+            });
         }
         let (header_from_value, value_without_header) = value.split_at(Png::STANDARD_HEADER.len());
         if header_from_value != Png::STANDARD_HEADER {
-            return Err("Header is Invalid!");
+            return Err(PngError::InvalidSignature);
         }
 
         let mut chunks: Vec<Chunk> = Vec::new();
@@ -70,14 +274,22 @@ impl TryFrom<&[u8]> for Png {
         while remaining_length > 0 {
             let chunk = Chunk::try_from(remaining_value)?;
 
-            (_, remaining_value) =
-                remaining_value.split_at((chunk.length() + 12).try_into().unwrap());
+            // Chunk::try_from succeeded, so the whole chunk is present in remaining_value
+            remaining_value = &remaining_value[chunk.length() as usize + Chunk::METADATA_LENGTH..];
 
+            let is_end = chunk.chunk_type().bytes() == *b"IEND";
             chunks.push(chunk);
+            // Whatever follows IEND isn't chunks, it is kept as trailing data
+            if is_end {
+                break;
+            }
 
             remaining_length = remaining_value.len();
         }
-        Ok(Self { signature: chunks })
+        Ok(Self {
+            signature: chunks,
+            trailing_data: remaining_value.to_vec(),
+        })
     }
 }
 
@@ -96,13 +308,11 @@ mod tests {
     use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::with_capacity(3);
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -114,7 +324,7 @@ mod tests {
         let chunk_type = ChunkType::from_str(chunk_type);
         let data: Vec<u8> = data.bytes().collect();
 
-        Ok(Chunk::new(chunk_type.unwrap(), data))
+        Ok(Chunk::try_new(chunk_type.unwrap(), data).unwrap())
     }
 
     #[test]
@@ -159,6 +369,7 @@ mod tests {
         let png = Png::try_from(bytes.as_ref());
 
         assert!(png.is_err());
+        assert!(matches!(png, Err(PngError::InvalidSignature)));
     }
 
     #[test]
@@ -183,6 +394,39 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_file_never_panics() {
+        for length in 0..PNG_FILE.len() {
+            let _ = Png::try_from(&PNG_FILE[..length]);
+        }
+        let png = Png::try_from(&PNG_FILE[..PNG_FILE.len() - 1]);
+        assert!(matches!(png, Err(PngError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_huge_declared_length() {
+        #[rustfmt::skip]
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .chain([
+                127, 255, 255, 255, // length (largest allowed, far beyond the input)
+                82, 117, 83, 116,   // Chunk Type
+                1, 2, 3, 4,         // Data
+            ].iter())
+            .copied()
+            .collect();
+
+        let png = Png::try_from(bytes.as_ref());
+
+        assert!(matches!(png, Err(PngError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_chunk_by_invalid_type() {
+        let png = testing_png();
+        assert!(png.chunk_by_type("F1St").is_none());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -198,6 +442,32 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am another first chunk").unwrap());
+        let chunks: Vec<&Chunk> = png.chunks_by_type("FrSt").collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].data_as_string().unwrap(), "I am the first chunk");
+        assert_eq!(
+            &chunks[1].data_as_string().unwrap(),
+            "I am another first chunk"
+        );
+
+        assert_eq!(png.chunks_by_type("TeSt").count(), 0);
+        assert_eq!(png.chunks_by_type("F1St").count(), 0);
+    }
+
+    #[test]
+    fn test_critical_canonical_bytes() {
+        let png = testing_png();
+        let expected: Vec<u8> = [&png.chunks()[0], &png.chunks()[2]]
+            .iter()
+            .flat_map(|chunk| chunk.canonical_bytes())
+            .collect();
+        assert_eq!(png.critical_canonical_bytes(), expected);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -207,6 +477,60 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = testing_png();
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        assert_eq!(png.chunks().len(), 4);
+
+        png.insert_chunk_at(4, chunk_from_strings("TeSt", "End").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[4].data_as_string().unwrap(), "End");
+    }
+
+    #[test]
+    fn test_insert_chunk_out_of_bounds() {
+        let mut png = testing_png();
+        let result = png.insert_chunk_at(4, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(
+            result,
+            Err(PngError::IndexOutOfBounds {
+                index: 4,
+                length: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_insert_before() {
+        let mut png = testing_png();
+        png.insert_before("LASt", chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        let chunks = png.chunks();
+        assert_eq!(&chunks[2].chunk_type().to_string(), "TeSt");
+        assert_eq!(&chunks[3].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_insert_after() {
+        let mut png = testing_png();
+        png.insert_after("FrSt", chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        let chunks = png.chunks();
+        assert_eq!(&chunks[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(&chunks[1].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_insert_before_missing_chunk() {
+        let mut png = testing_png();
+        let result = png.insert_before("IEND", chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(PngError::ChunkNotFound(_))));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -216,18 +540,158 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        let removed = png.remove_first_chunk("TeSt");
+        assert!(matches!(removed, Err(PngError::ChunkNotFound(_))));
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        png.insert_chunk_at(0, chunk_from_strings("TeSt", "One").unwrap())
+            .unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Two").unwrap());
+        let removed = png.remove_all_chunks("TeSt").unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[0].data_as_string().unwrap(), "One");
+        assert_eq!(&removed[1].data_as_string().unwrap(), "Two");
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type("TeSt").is_none());
+    }
+
+    #[test]
+    fn test_remove_all_missing_chunks() {
+        let mut png = testing_png();
+        let removed = png.remove_all_chunks("TeSt");
+        assert!(matches!(removed, Err(PngError::ChunkNotFound(_))));
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (50, 50));
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, crate::ihdr::ColorType::Rgba);
+
+        assert!(matches!(
+            testing_png().ihdr(),
+            Err(PngError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_pixels_from_image_file() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let pixels = png.pixels().unwrap();
+
+        let mut decoder = png::Decoder::new(&PNG_FILE[..]);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let mut expected = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut expected).unwrap();
+        assert_eq!(pixels.data(), expected);
+        assert_eq!(pixels.row_length(), 50 * 4);
+    }
+
+    #[test]
+    fn test_from_pixels_palette_rules() {
+        use crate::ihdr::Interlace;
+        use crate::pixels::EncodeOptions;
+
+        let indexed = Ihdr::new(2, 1, 8, ColorType::Indexed, Interlace::None).unwrap();
+        let pixels = PixelBuffer::new(indexed, vec![0, 2]).unwrap();
+        let options = EncodeOptions::default();
+        let palette = [[0, 0, 0], [255, 0, 0], [0, 255, 0]];
+
+        let png = Png::from_pixels(&pixels, Some(&palette), options).unwrap();
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, ["IHDR", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(png.chunk_by_type("PLTE").unwrap().data().len(), 9);
+
+        assert!(Png::from_pixels(&pixels, None, options).is_err());
+        assert!(Png::from_pixels(&pixels, Some(&palette[..2]), options).is_err());
+
+        let grayscale = Ihdr::new(2, 1, 8, ColorType::Grayscale, Interlace::None).unwrap();
+        let pixels = PixelBuffer::new(grayscale, vec![0, 2]).unwrap();
+        assert!(Png::from_pixels(&pixels, Some(&palette), options).is_err());
+    }
+
+    #[test]
+    fn test_replace_pixels() {
+        use crate::pixels::EncodeOptions;
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut pixels = png.pixels().unwrap();
+        pixels.data_mut()[0] ^= 0xff;
+        png.replace_pixels(&pixels, EncodeOptions::default())
+            .unwrap();
+
+        assert_eq!(png.pixels().unwrap(), pixels);
+        let chunk_types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+        );
+
+        let other = PixelBuffer::new(
+            Ihdr::new(1, 1, 8, ColorType::Grayscale, crate::ihdr::Interlace::None).unwrap(),
+            vec![0],
+        )
+        .unwrap();
+        assert!(png
+            .replace_pixels(&other, EncodeOptions::default())
+            .is_err());
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
+        assert!(png.trailing_data().is_empty());
+    }
+
+    #[test]
+    fn test_trailing_data_round_trip() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Trailing data that looks like the start of a chunk must not be parsed as one
+        bytes.extend_from_slice(&[0, 0, 0, 4, b'r', b'u', b'S', b't', 1, 2]);
+
+        let png = Png::try_from(&bytes[..]).unwrap();
+        assert_eq!(png.trailing_data(), &bytes[PNG_FILE.len()..]);
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert_eq!(png.as_bytes(), bytes);
+
+        let png = Png::read_from(&bytes[..]).unwrap();
+        assert_eq!(png.trailing_data(), &bytes[PNG_FILE.len()..]);
+        assert_eq!(png.as_bytes(), bytes);
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.set_trailing_data(b"appended".to_vec());
+        assert!(png.as_bytes().ends_with(b"IEND\xaeB`\x82appended"));
     }
 
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
@@ -0,0 +1,263 @@
+use std::io::Read;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{PngError, Result},
+    png::Png,
+};
+
+/// Reads a PNG from any `Read` source one chunk at a time, so the whole file never has to be in memory.
+///
+/// The signature is checked by `PngReader::new`, then chunks are yielded by `read_chunk`
+/// or by iterating over the reader. Reading stops after `IEND`: any trailing data is left in
+/// the source, which `into_inner` hands back.
+#[derive(Debug)]
+pub struct PngReader<R: Read> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: Read> PngReader<R> {
+    pub fn new(mut reader: R) -> Result<PngReader<R>> {
+        let mut header = [0; 8];
+        let read = read_up_to(&mut reader, &mut header)?;
+        if read < header.len() {
+            return Err(PngError::Truncated {
+                needed: header.len(),
+                available: read,
+            });
+        }
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature);
+        }
+
+        Ok(PngReader {
+            reader,
+            finished: false,
+        })
+    }
+
+    /// Reads the next chunk, or returns `None` once `IEND` was read or the input ends cleanly
+    /// between two chunks.
+    pub fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let result = self.read_next();
+        self.finished = match &result {
+            Ok(Some(chunk)) => chunk.chunk_type().bytes() == *b"IEND",
+            _ => true,
+        };
+        result
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_next(&mut self) -> Result<Option<Chunk>> {
+        // Length (4) + Chunk Type (4)
+        let mut length_and_type = [0; 8];
+        let read = read_up_to(&mut self.reader, &mut length_and_type)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < length_and_type.len() {
+            return Err(PngError::Truncated {
+                needed: length_and_type.len(),
+                available: read,
+            });
+        }
+        let data_length = u32::from_be_bytes([
+            length_and_type[0],
+            length_and_type[1],
+            length_and_type[2],
+            length_and_type[3],
+        ]);
+        let chunk_type = ChunkType::try_from([
+            length_and_type[4],
+            length_and_type[5],
+            length_and_type[6],
+            length_and_type[7],
+        ])?;
+        if data_length as usize > Chunk::MAX_LENGTH {
+            return Err(PngError::ChunkTooLarge(data_length as usize));
+        }
+
+        // take() makes the buffer grow with the bytes actually present instead of trusting the declared length
+        let mut data = Vec::new();
+        (&mut self.reader)
+            .take(data_length as u64)
+            .read_to_end(&mut data)?;
+        if data.len() < data_length as usize {
+            return Err(PngError::Truncated {
+                needed: data_length as usize,
+                available: data.len(),
+            });
+        }
+
+        let mut crc_bytes = [0; 4];
+        let read = read_up_to(&mut self.reader, &mut crc_bytes)?;
+        if read < crc_bytes.len() {
+            return Err(PngError::Truncated {
+                needed: crc_bytes.len(),
+                available: read,
+            });
+        }
+        let crc_val_from_bytes = u32::from_be_bytes(crc_bytes);
+
+        let chunk = Chunk::try_new(chunk_type, data)?;
+        if chunk.crc() != crc_val_from_bytes {
+            return Err(PngError::CrcMismatch {
+                expected: chunk.crc(),
+                actual: crc_val_from_bytes,
+            });
+        }
+
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for PngReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_chunk().transpose()
+    }
+}
+
+/// Like `read_exact`, but reports how many bytes were read when the input ends early.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(read) => total += read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::try_new(
+                ChunkType::from_str("FrSt").unwrap(),
+                b"I am the first chunk".to_vec(),
+            )
+            .unwrap(),
+            Chunk::try_new(
+                ChunkType::from_str("miDl").unwrap(),
+                b"I am another chunk".to_vec(),
+            )
+            .unwrap(),
+            Chunk::try_new(
+                ChunkType::from_str("LASt").unwrap(),
+                b"I am the last chunk".to_vec(),
+            )
+            .unwrap(),
+        ];
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    /// Hands out a single byte per read call, like a slow pipe.
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_read_chunks() {
+        let bytes = testing_png_bytes();
+        let reader = PngReader::new(&bytes[..]).unwrap();
+        let chunks: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].chunk_type().to_string(), "miDl");
+        assert_eq!(chunks[1].data_as_string().unwrap(), "I am another chunk");
+    }
+
+    #[test]
+    fn test_read_chunks_byte_by_byte() {
+        let bytes = testing_png_bytes();
+        let reader = PngReader::new(OneByteReader(&bytes)).unwrap();
+        let chunks: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].data_as_string().unwrap(), "I am the last chunk");
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = testing_png_bytes();
+        bytes[0] = 13;
+        let reader = PngReader::new(&bytes[..]);
+
+        assert!(matches!(reader, Err(PngError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_png_bytes();
+        let mut reader = PngReader::new(&bytes[..bytes.len() - 2]).unwrap();
+
+        assert!(reader.read_chunk().unwrap().is_some());
+        assert!(reader.read_chunk().unwrap().is_some());
+        assert!(matches!(
+            reader.read_chunk(),
+            Err(PngError::Truncated { .. })
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        let mut bytes = testing_png_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let reader = PngReader::new(&bytes[..]).unwrap();
+        let result: Result<Vec<Chunk>> = reader.collect();
+
+        assert!(matches!(result, Err(PngError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_stops_after_iend() {
+        let mut bytes = Png::from_chunks(vec![
+            Chunk::try_new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()).unwrap(),
+            Chunk::try_new(ChunkType::from_str("IEND").unwrap(), Vec::new()).unwrap(),
+        ])
+        .as_bytes();
+        bytes.extend_from_slice(b"not a chunk");
+        let mut reader = PngReader::new(&bytes[..]).unwrap();
+
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.into_inner(), b"not a chunk");
+    }
+
+    #[test]
+    fn test_read_matches_try_from() {
+        let bytes = testing_png_bytes();
+        let png = Png::read_from(&bytes[..]).unwrap();
+
+        assert_eq!(
+            png.as_bytes(),
+            Png::try_from(&bytes[..]).unwrap().as_bytes()
+        );
+    }
+}
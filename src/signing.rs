@@ -0,0 +1,273 @@
+use ed25519_dalek::{Signature, Signer};
+use rand_core::OsRng;
+
+use crate::{
+    byte_reader::ByteReader,
+    crypto::key_from_hex,
+    error::{PngError, Result},
+};
+
+// Signed data layout:
+// magic (4) | version (1) | flags (1) | signer public key (32) | signature (64) | payload
+// The signature covers a domain separator, the flags, the payload and, when FLAG_COVERS_IMAGE
+// is set, the canonical bytes of the image's critical chunks (see `Png::critical_canonical_bytes`).
+const MAGIC: [u8; 4] = *b"PMSG";
+const VERSION: u8 = 1;
+const FLAG_COVERS_IMAGE: u8 = 1;
+const DOMAIN: &[u8] = b"pngme signature v1";
+const KEY_LENGTH: usize = 32;
+const SIGNATURE_LENGTH: usize = 64;
+
+/// Bytes `sign` adds to the payload
+pub const OVERHEAD: usize = 4 + 1 + 1 + KEY_LENGTH + SIGNATURE_LENGTH;
+
+/// Ed25519 private key used to sign embedded messages.
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    pub fn generate() -> SigningKey {
+        SigningKey(ed25519_dalek::SigningKey::generate(&mut OsRng))
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.to_bytes())
+    }
+
+    pub fn from_hex(key: &str) -> Result<SigningKey> {
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(
+            &key_from_hex(key)?,
+        )))
+    }
+}
+
+/// Ed25519 public key that signatures are checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_bytes())
+    }
+
+    pub fn from_hex(key: &str) -> Result<VerifyingKey> {
+        VerifyingKey::from_bytes(&key_from_hex(key)?)
+    }
+
+    fn from_bytes(bytes: &[u8; KEY_LENGTH]) -> Result<VerifyingKey> {
+        ed25519_dalek::VerifyingKey::from_bytes(bytes)
+            .map(VerifyingKey)
+            .map_err(|err| PngError::InvalidKey(err.to_string()))
+    }
+}
+
+/// Returns true if `data` starts with the header written by `sign`
+pub fn is_signed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Wraps `payload` with an Ed25519 signature by `key`.
+///
+/// When `image` holds the canonical bytes of the carrier's critical chunks, the signature covers
+/// them too, so the message can't be moved to a different image without invalidating it.
+pub fn sign(payload: &[u8], key: &SigningKey, image: Option<&[u8]>) -> Vec<u8> {
+    let flags = if image.is_some() {
+        FLAG_COVERS_IMAGE
+    } else {
+        0
+    };
+    let signature = key.0.sign(&signed_message(flags, payload, image));
+
+    let mut signed = Vec::with_capacity(payload.len() + 6 + KEY_LENGTH + SIGNATURE_LENGTH);
+    signed.extend_from_slice(&MAGIC);
+    signed.push(VERSION);
+    signed.push(flags);
+    signed.extend_from_slice(key.verifying_key().0.as_bytes());
+    signed.extend_from_slice(&signature.to_bytes());
+    signed.extend_from_slice(payload);
+    signed
+}
+
+/// A payload wrapped by `sign`, parsed but not yet verified.
+#[derive(Debug)]
+pub struct SignedPayload<'a> {
+    signer: VerifyingKey,
+    flags: u8,
+    signature: Signature,
+    payload: &'a [u8],
+}
+
+impl<'a> SignedPayload<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<SignedPayload<'a>> {
+        let mut reader = ByteReader::new(data);
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(PngError::MalformedPayload("data is not signed".to_string()));
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(PngError::MalformedPayload(format!(
+                "unsupported signature version {}",
+                version
+            )));
+        }
+        let flags = reader.read_u8()?;
+        let signer = VerifyingKey::from_bytes(&reader.read_array()?)?;
+        let signature = Signature::from_bytes(&reader.read_array()?);
+
+        Ok(SignedPayload {
+            signer,
+            flags,
+            signature,
+            payload: reader.rest(),
+        })
+    }
+
+    /// Key that claims to have signed the payload. Only trust it after `verify` succeeds.
+    pub fn signer(&self) -> &VerifyingKey {
+        &self.signer
+    }
+
+    /// The data that was signed, without the signature header
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    pub fn covers_image(&self) -> bool {
+        self.flags & FLAG_COVERS_IMAGE != 0
+    }
+
+    /// Checks that the signer is one of `trusted_keys` and that the signature is valid.
+    /// `image` must be given when the signature covers the image's critical chunks.
+    pub fn verify(&self, trusted_keys: &[VerifyingKey], image: Option<&[u8]>) -> Result<()> {
+        if !trusted_keys.contains(&self.signer) {
+            return Err(PngError::UntrustedSigner(self.signer.to_hex()));
+        }
+        let image = match (self.covers_image(), image) {
+            (true, None) => {
+                return Err(PngError::MalformedPayload(
+                    "signature covers the image but no image was given".to_string(),
+                ))
+            }
+            (true, image) => image,
+            (false, _) => None,
+        };
+        self.signer
+            .0
+            .verify_strict(
+                &signed_message(self.flags, self.payload, image),
+                &self.signature,
+            )
+            .map_err(|_| PngError::SignatureVerificationFailed)
+    }
+}
+
+fn signed_message(flags: u8, payload: &[u8], image: Option<&[u8]>) -> Vec<u8> {
+    let image = image.unwrap_or_default();
+    let mut message = Vec::with_capacity(DOMAIN.len() + 1 + 8 + payload.len() + image.len());
+    message.extend_from_slice(DOMAIN);
+    message.push(flags);
+    // The payload length keeps the payload/image boundary unambiguous
+    message.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    message.extend_from_slice(payload);
+    message.extend_from_slice(image);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::generate();
+        let signed = sign(b"secret message", &key, None);
+
+        assert!(is_signed(&signed));
+        assert_eq!(signed.len(), 14 + OVERHEAD);
+        let signed_payload = SignedPayload::parse(&signed).unwrap();
+        assert_eq!(signed_payload.payload(), b"secret message");
+        assert_eq!(*signed_payload.signer(), key.verifying_key());
+        assert!(!signed_payload.covers_image());
+        signed_payload.verify(&[key.verifying_key()], None).unwrap();
+    }
+
+    #[test]
+    fn test_untrusted_signer() {
+        let key = SigningKey::generate();
+        let other_key = SigningKey::generate();
+        let signed = sign(b"secret message", &key, None);
+        let result = SignedPayload::parse(&signed)
+            .unwrap()
+            .verify(&[other_key.verifying_key()], None);
+
+        assert!(matches!(result, Err(PngError::UntrustedSigner(_))));
+    }
+
+    #[test]
+    fn test_tampered_payload() {
+        let key = SigningKey::generate();
+        let mut signed = sign(b"secret message", &key, None);
+        let last = signed.len() - 1;
+        signed[last] ^= 1;
+        let result = SignedPayload::parse(&signed)
+            .unwrap()
+            .verify(&[key.verifying_key()], None);
+
+        assert!(matches!(result, Err(PngError::SignatureVerificationFailed)));
+    }
+
+    #[test]
+    fn test_signature_covers_image() {
+        let key = SigningKey::generate();
+        let signed = sign(b"secret message", &key, Some(b"critical chunks"));
+        let signed_payload = SignedPayload::parse(&signed).unwrap();
+
+        assert!(signed_payload.covers_image());
+        signed_payload
+            .verify(&[key.verifying_key()], Some(b"critical chunks"))
+            .unwrap();
+        assert!(matches!(
+            signed_payload.verify(&[key.verifying_key()], Some(b"other chunks")),
+            Err(PngError::SignatureVerificationFailed)
+        ));
+        assert!(matches!(
+            signed_payload.verify(&[key.verifying_key()], None),
+            Err(PngError::MalformedPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_cleared_image_flag() {
+        let key = SigningKey::generate();
+        let mut signed = sign(b"secret message", &key, Some(b"critical chunks"));
+        // Dropping the flag must not turn the signature into a payload-only one
+        signed[5] = 0;
+        let result = SignedPayload::parse(&signed)
+            .unwrap()
+            .verify(&[key.verifying_key()], None);
+
+        assert!(matches!(result, Err(PngError::SignatureVerificationFailed)));
+    }
+
+    #[test]
+    fn test_key_hex_round_trip() {
+        let key = SigningKey::generate();
+        let restored = SigningKey::from_hex(&key.to_hex()).unwrap();
+        assert_eq!(restored.verifying_key(), key.verifying_key());
+
+        let verifying_key = VerifyingKey::from_hex(&key.verifying_key().to_hex()).unwrap();
+        assert_eq!(verifying_key, key.verifying_key());
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        let key = SigningKey::generate();
+        let signed = sign(b"secret message", &key, None);
+        for length in 0..6 + KEY_LENGTH + SIGNATURE_LENGTH {
+            assert!(SignedPayload::parse(&signed[..length]).is_err());
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers shared by the unit tests.
+
+/// Marsaglia's xorshift32, a deterministic stand-in for noise in test images and payloads.
+pub(crate) struct XorShift(pub(crate) u32);
+
+impl XorShift {
+    pub(crate) fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// `length` bytes of noise, the same for the same `seed`
+pub(crate) fn noise(seed: u32, length: usize) -> Vec<u8> {
+    let mut rng = XorShift(seed);
+    (0..length).map(|_| rng.next() as u8).collect()
+}
+
+/// The PngSuite conformance images, as (file name, contents). Files starting with `x` are
+/// deliberately corrupted.
+pub(crate) fn pngsuite() -> Vec<(String, Vec<u8>)> {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/pngsuite");
+    let mut files: Vec<(String, Vec<u8>)> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read(path).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
@@ -0,0 +1,364 @@
+use std::str::FromStr;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{self, DEFAULT_MAX_DECOMPRESSED_LENGTH},
+    error::{PngError, Result},
+};
+
+const TEXT: &str = "tEXt";
+const COMPRESSED_TEXT: &str = "zTXt";
+const INTERNATIONAL_TEXT: &str = "iTXt";
+// zlib is the only compression method the PNG specification defines
+const COMPRESSION_METHOD_ZLIB: u8 = 0;
+const MAX_KEYWORD_LENGTH: usize = 79;
+
+/// Which of the three PNG text chunks a `TextChunk` is stored as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextKind {
+    /// `tEXt`: uncompressed Latin-1 text
+    Text,
+    /// `zTXt`: zlib-compressed Latin-1 text
+    Compressed,
+    /// `iTXt`: UTF-8 text, optionally compressed, with a language tag and a translation of the keyword
+    International {
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+    },
+}
+
+/// Keyword and text stored in a `tEXt`, `zTXt` or `iTXt` chunk, as defined by the PNG specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+    pub kind: TextKind,
+}
+
+impl TextChunk {
+    /// Builds a `tEXt` chunk when `text` fits in Latin-1 and an uncompressed `iTXt` chunk otherwise
+    pub fn new(keyword: &str, text: &str) -> TextChunk {
+        let kind = if to_latin1(text).is_some() {
+            TextKind::Text
+        } else {
+            TextKind::International {
+                compressed: false,
+                language_tag: String::new(),
+                translated_keyword: String::new(),
+            }
+        };
+        TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            kind,
+        }
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        let chunk_type = match self.kind {
+            TextKind::Text => TEXT,
+            TextKind::Compressed => COMPRESSED_TEXT,
+            TextKind::International { .. } => INTERNATIONAL_TEXT,
+        };
+        ChunkType::from_str(chunk_type).expect("text chunk types are valid")
+    }
+
+    /// Encodes the chunk, checking the keyword, language tag and text against the specification
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = validate_keyword(&self.keyword)?;
+        data.push(0);
+        match &self.kind {
+            TextKind::Text => {
+                let text = latin1_text(&self.text)?;
+                if text.contains(&0) {
+                    return Err(PngError::InvalidTextChunk(
+                        "tEXt text can't contain null characters".to_string(),
+                    ));
+                }
+                data.extend_from_slice(&text);
+            }
+            TextKind::Compressed => {
+                data.push(COMPRESSION_METHOD_ZLIB);
+                data.extend_from_slice(&compression::zlib_compress(&latin1_text(&self.text)?)?);
+            }
+            TextKind::International {
+                compressed,
+                language_tag,
+                translated_keyword,
+            } => {
+                validate_language_tag(language_tag)?;
+                if translated_keyword.contains('\0') {
+                    return Err(PngError::InvalidTextChunk(
+                        "translated keyword can't contain null characters".to_string(),
+                    ));
+                }
+                data.push(u8::from(*compressed));
+                data.push(COMPRESSION_METHOD_ZLIB);
+                data.extend_from_slice(language_tag.as_bytes());
+                data.push(0);
+                data.extend_from_slice(translated_keyword.as_bytes());
+                data.push(0);
+                if *compressed {
+                    data.extend_from_slice(&compression::zlib_compress(self.text.as_bytes())?);
+                } else {
+                    data.extend_from_slice(self.text.as_bytes());
+                }
+            }
+        }
+        Chunk::try_new(self.chunk_type(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<TextChunk> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let (keyword, rest) = split_at_null(chunk.data(), "keyword")?;
+        if keyword.is_empty() || keyword.len() > MAX_KEYWORD_LENGTH {
+            return Err(PngError::InvalidTextChunk(format!(
+                "keyword must be 1 to {} bytes long",
+                MAX_KEYWORD_LENGTH
+            )));
+        }
+        let keyword = from_latin1(keyword);
+
+        let (text, kind) = match chunk_type.as_str() {
+            TEXT => (from_latin1(rest), TextKind::Text),
+            COMPRESSED_TEXT => {
+                let (&method, compressed_text) = rest.split_first().ok_or_else(|| {
+                    PngError::InvalidTextChunk("missing compression method".to_string())
+                })?;
+                check_compression_method(method)?;
+                let text =
+                    compression::zlib_decompress(compressed_text, DEFAULT_MAX_DECOMPRESSED_LENGTH)?;
+                (from_latin1(&text), TextKind::Compressed)
+            }
+            INTERNATIONAL_TEXT => {
+                let [compressed, method, rest @ ..] = rest else {
+                    return Err(PngError::InvalidTextChunk(
+                        "missing compression flag or method".to_string(),
+                    ));
+                };
+                let compressed = match compressed {
+                    0 => false,
+                    1 => true,
+                    flag => {
+                        return Err(PngError::InvalidTextChunk(format!(
+                            "invalid compression flag {}",
+                            flag
+                        )))
+                    }
+                };
+                check_compression_method(*method)?;
+                let (language_tag, rest) = split_at_null(rest, "language tag")?;
+                let (translated_keyword, text) = split_at_null(rest, "translated keyword")?;
+                let text = if compressed {
+                    String::from_utf8(compression::zlib_decompress(
+                        text,
+                        DEFAULT_MAX_DECOMPRESSED_LENGTH,
+                    )?)
+                    .map_err(|err| PngError::InvalidUtf8(err.utf8_error()))?
+                } else {
+                    std::str::from_utf8(text)?.to_string()
+                };
+                (
+                    text,
+                    TextKind::International {
+                        compressed,
+                        language_tag: std::str::from_utf8(language_tag)?.to_string(),
+                        translated_keyword: std::str::from_utf8(translated_keyword)?.to_string(),
+                    },
+                )
+            }
+            _ => {
+                return Err(PngError::InvalidTextChunk(format!(
+                    "{} is not a text chunk",
+                    chunk_type
+                )))
+            }
+        };
+
+        Ok(TextChunk {
+            keyword,
+            text,
+            kind,
+        })
+    }
+}
+
+/// Returns true for the `tEXt`, `zTXt` and `iTXt` chunk types
+pub fn is_text_chunk_type(chunk_type: &ChunkType) -> bool {
+    [TEXT, COMPRESSED_TEXT, INTERNATIONAL_TEXT].contains(&chunk_type.to_string().as_str())
+}
+
+fn split_at_null<'a>(data: &'a [u8], field: &str) -> Result<(&'a [u8], &'a [u8])> {
+    let position = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| PngError::InvalidTextChunk(format!("{} is not null-terminated", field)))?;
+    Ok((&data[..position], &data[position + 1..]))
+}
+
+fn check_compression_method(method: u8) -> Result<()> {
+    if method != COMPRESSION_METHOD_ZLIB {
+        return Err(PngError::InvalidTextChunk(format!(
+            "unknown compression method {}",
+            method
+        )));
+    }
+    Ok(())
+}
+
+/// Keywords are 1-79 printable Latin-1 characters without leading, trailing or consecutive spaces
+fn validate_keyword(keyword: &str) -> Result<Vec<u8>> {
+    let invalid = |reason: &str| {
+        Err(PngError::InvalidTextChunk(format!(
+            "keyword {:?} {}",
+            keyword, reason
+        )))
+    };
+    let Some(bytes) = to_latin1(keyword) else {
+        return invalid("is not Latin-1");
+    };
+    if bytes.is_empty() || bytes.len() > MAX_KEYWORD_LENGTH {
+        return invalid("must be 1 to 79 characters long");
+    }
+    if !bytes
+        .iter()
+        .all(|&byte| matches!(byte, 32..=126 | 161..=255))
+    {
+        return invalid("contains a non-printable character");
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return invalid("has leading, trailing or consecutive spaces");
+    }
+    Ok(bytes)
+}
+
+/// Language tags (RFC 3066) are ASCII letters, digits and hyphens, and may be empty
+fn validate_language_tag(language_tag: &str) -> Result<()> {
+    if !language_tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(PngError::InvalidTextChunk(format!(
+            "invalid language tag {:?}",
+            language_tag
+        )));
+    }
+    Ok(())
+}
+
+fn latin1_text(text: &str) -> Result<Vec<u8>> {
+    to_latin1(text).ok_or_else(|| {
+        PngError::InvalidTextChunk(
+            "text is not Latin-1, store it in an iTXt chunk instead".to_string(),
+        )
+    })
+}
+
+fn to_latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().copied().map(char::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text_chunk: &TextChunk) -> TextChunk {
+        TextChunk::try_from(&text_chunk.to_chunk().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_text_chunk_layout() {
+        let chunk = TextChunk::new("Comment", "café").to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Comment\0caf\xe9");
+    }
+
+    #[test]
+    fn test_round_trip_all_kinds() {
+        let kinds = [
+            TextKind::Text,
+            TextKind::Compressed,
+            TextKind::International {
+                compressed: false,
+                language_tag: "fr-CA".to_string(),
+                translated_keyword: "Auteur".to_string(),
+            },
+            TextKind::International {
+                compressed: true,
+                language_tag: String::new(),
+                translated_keyword: String::new(),
+            },
+        ];
+        for kind in kinds {
+            let text_chunk = TextChunk {
+                keyword: "Author".to_string(),
+                text: "Ünïcödé text ".repeat(20),
+                kind,
+            };
+            assert_eq!(round_trip(&text_chunk), text_chunk);
+        }
+    }
+
+    #[test]
+    fn test_non_latin1_text_uses_itxt() {
+        let text_chunk = TextChunk::new("Title", "水の音");
+        assert_eq!(text_chunk.chunk_type().to_string(), "iTXt");
+        assert_eq!(round_trip(&text_chunk), text_chunk);
+
+        let forced_text = TextChunk {
+            kind: TextKind::Text,
+            ..text_chunk
+        };
+        assert!(matches!(
+            forced_text.to_chunk(),
+            Err(PngError::InvalidTextChunk(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        let too_long = "k".repeat(80);
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Two  spaces",
+            "Tab\tkey",
+            "水",
+            &too_long,
+        ] {
+            assert!(
+                TextChunk::new(keyword, "text").to_chunk().is_err(),
+                "{:?} should be rejected",
+                keyword
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let text = ChunkType::from_str("tEXt").unwrap();
+        let international = ChunkType::from_str("iTXt").unwrap();
+        let other = ChunkType::from_str("ruSt").unwrap();
+
+        let missing_null = Chunk::try_new(text, b"Comment".to_vec()).unwrap();
+        assert!(TextChunk::try_from(&missing_null).is_err());
+        let bad_flag =
+            Chunk::try_new(international, b"Comment\x02\x00\x00\x00text".to_vec()).unwrap();
+        assert!(TextChunk::try_from(&bad_flag).is_err());
+        let truncated = Chunk::try_new(international, b"Comment\x00\x00\x00en".to_vec()).unwrap();
+        assert!(TextChunk::try_from(&truncated).is_err());
+        let not_text = Chunk::try_new(other, b"Comment\0text".to_vec()).unwrap();
+        assert!(TextChunk::try_from(&not_text).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    byte_reader::ByteReader,
+    error::{PngError, Result},
+};
+
+// Trailer layout, written right after `IEND`:
+// magic (4) | version (1) | message length (4) | message
+// The trailer only frames the message so it can be told apart from other trailing data. It is
+// not authenticated itself, which is why `encode --method trailer` requires passphrase
+// encryption of the message.
+const MAGIC: [u8; 4] = *b"PMTR";
+const VERSION: u8 = 1;
+/// Bytes taken by the trailer header in front of the message
+pub const HEADER_LENGTH: usize = 9;
+
+/// Returns true if `data` starts with the header written by `wrap`
+pub fn is_trailer(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Frames `message` to be stored after `IEND`
+pub fn wrap(message: &[u8]) -> Result<Vec<u8>> {
+    let length = u32::try_from(message.len())
+        .map_err(|_| PngError::MalformedPayload("message is too long for a trailer".to_string()))?;
+    let mut trailer = Vec::with_capacity(HEADER_LENGTH + message.len());
+    trailer.extend_from_slice(&MAGIC);
+    trailer.push(VERSION);
+    trailer.extend_from_slice(&length.to_be_bytes());
+    trailer.extend_from_slice(message);
+    Ok(trailer)
+}
+
+/// Reverses `wrap`, returning the message. Bytes after the message are ignored, since other
+/// tools may append their own data to the file.
+pub fn parse(data: &[u8]) -> Result<&[u8]> {
+    let mut reader = ByteReader::new(data);
+    if reader.read_array::<4>()? != MAGIC {
+        return Err(PngError::MalformedPayload(
+            "the data after IEND is not a trailer".to_string(),
+        ));
+    }
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(PngError::MalformedPayload(format!(
+            "unsupported trailer version {}",
+            version
+        )));
+    }
+    let length = reader.read_u32()? as usize;
+    reader.read_bytes(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let trailer = wrap(b"secret message").unwrap();
+        assert!(is_trailer(&trailer));
+        assert_eq!(trailer.len(), HEADER_LENGTH + b"secret message".len());
+        assert_eq!(parse(&trailer).unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_data_after_the_message() {
+        let mut trailer = wrap(b"secret message").unwrap();
+        trailer.extend_from_slice(b"appended by another tool");
+        assert_eq!(parse(&trailer).unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_truncated_trailer() {
+        let trailer = wrap(b"secret message").unwrap();
+        assert!(matches!(
+            parse(&trailer[..trailer.len() - 1]),
+            Err(PngError::Truncated {
+                needed: 14,
+                available: 13
+            })
+        ));
+        assert!(parse(&trailer[..6]).is_err());
+    }
+
+    #[test]
+    fn test_not_a_trailer() {
+        assert!(!is_trailer(b"GIF89a"));
+        assert!(matches!(
+            parse(b"GIF89a and more"),
+            Err(PngError::MalformedPayload(_))
+        ));
+    }
+}
@@ -0,0 +1,96 @@
+use std::io::Write;
+
+use crate::{chunk::Chunk, error::Result, png::Png};
+
+/// Writes a PNG to any `Write` sink one chunk at a time, without building the file in memory.
+///
+/// The signature is written by `PngWriter::new`, chunks are serialized straight into the sink
+/// by `write_chunk`, and `finish` flushes and hands the sink back.
+#[derive(Debug)]
+pub struct PngWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PngWriter<W> {
+    pub fn new(mut writer: W) -> Result<PngWriter<W>> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+        Ok(PngWriter { writer })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        chunk.write_to(&mut self.writer)
+    }
+
+    pub fn write_chunks<'a>(&mut self, chunks: impl IntoIterator<Item = &'a Chunk>) -> Result<()> {
+        for chunk in chunks {
+            self.write_chunk(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Writes bytes after the last chunk, as some files carry after `IEND`
+    pub fn write_trailing_data(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, reader::PngReader};
+    use std::str::FromStr;
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            Chunk::try_new(
+                ChunkType::from_str("FrSt").unwrap(),
+                b"I am the first chunk".to_vec(),
+            )
+            .unwrap(),
+            Chunk::try_new(
+                ChunkType::from_str("LASt").unwrap(),
+                b"I am the last chunk".to_vec(),
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_write_chunks() {
+        let chunks = testing_chunks();
+        let mut png_writer = PngWriter::new(Vec::new()).unwrap();
+        png_writer.write_chunks(&chunks).unwrap();
+        let bytes = png_writer.finish().unwrap();
+
+        assert_eq!(bytes, Png::from_chunks(chunks).as_bytes());
+    }
+
+    #[test]
+    fn test_empty_png_is_signature() {
+        let bytes = PngWriter::new(Vec::new()).unwrap().finish().unwrap();
+        assert_eq!(bytes, Png::STANDARD_HEADER);
+    }
+
+    #[test]
+    fn test_round_trip_with_reader() {
+        let chunks = testing_chunks();
+        let mut bytes = Vec::new();
+        Png::from_chunks(chunks.clone())
+            .write_to(&mut bytes)
+            .unwrap();
+
+        let read: Vec<Chunk> = PngReader::new(&bytes[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), chunks.len());
+        assert_eq!(read[0].as_bytes(), chunks[0].as_bytes());
+        assert_eq!(read[1].as_bytes(), chunks[1].as_bytes());
+    }
+}